@@ -0,0 +1,58 @@
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use script::token::tokenize_reader;
+
+use rustic_tales::options::BufOptions;
+use rustic_tales::storyteller::story::Story;
+
+// About as long as a novel (~1MB), with a little bit of everything Script has sprinkled in.
+// Set BENCH_BOOK to the path of a real book to use that instead.
+fn book() -> PathBuf {
+    if let Ok(path) = std::env::var("BENCH_BOOK") {
+        return PathBuf::from(path);
+    }
+    let paragraph = "It was the best of times, it was the worst of times, it was the age of \
+                     wisdom, it was the age of foolishness. {c}Prices were $5 or $6, depending \
+                     on who you asked, and ${{NAME|nobody}} asked #twice.\n";
+    let mut book = String::from("{{{\ntitle : A Tale\n}}}\n");
+    for chapter in 0..25 {
+        book += &format!("#=$ chapter {} $=#\n", chapter);
+        for page in 0..20 {
+            book += &paragraph.repeat(10);
+            book += &format!("{{{{ pause : {}ms }}}}\n", page * 10);
+            book += "{{~ a note\nto self ~}}\n/PAGE/\n";
+        }
+    }
+    let path = std::env::temp_dir().join(format!("rustic_tales_{}_book.txt", std::process::id()));
+    fs::write(&path, book).expect("temp dir should be writable");
+    path
+}
+
+// Everything that happens before the first word gets told: reading the file (as it's tokenized,
+// see tokenize_reader), checking its commands and splitting it into pages. Tokenizing on its own
+// is timed too, to see how much of that it is.
+fn bench_load(c: &mut Criterion) {
+    let path = book();
+    let size = fs::metadata(&path).expect("could not read the book").len();
+    let mut group = c.benchmark_group("book");
+    group.throughput(Throughput::Bytes(size));
+    group.sample_size(20);
+    let tab_width = BufOptions::default().tab_width;
+    group.bench_function("Story::from_file", |b| {
+        b.iter(|| Story::from_file(&path, tab_width).expect("the book should load"))
+    });
+    group.bench_function("tokenize_reader", |b| {
+        b.iter(|| {
+            let file = File::open(&path).expect("could not read the book");
+            tokenize_reader(BufReader::new(file)).count()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_load);
+criterion_main!(benches);
@@ -1,7 +1,9 @@
 use std::collections::LinkedList;
 use std::fmt;
+use std::sync::OnceLock;
 
 use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::options::BufOptions;
 use crate::utils::*;
@@ -44,6 +46,7 @@ impl Color {
             base_val
         }
     }
+    #[allow(dead_code)]
     pub fn is_light(&self) -> bool {
         self.1
     }
@@ -100,21 +103,23 @@ impl CellModifier {
         use std::mem::transmute;
         match val {
             // SAFETY: #[repr(u8)] + bounds
-            n @ 0..=9 => Some(CellModifier::Effect(unsafe { transmute(n) })),
+            n @ 0..=9 => Some(CellModifier::Effect(unsafe {
+                transmute::<u8, TextEffect>(n)
+            })),
             n @ 30..=37 => Some(CellModifier::FGColor(Color(
-                unsafe { transmute(n - 30) },
+                unsafe { transmute::<u8, BaseColor>(n - 30) },
                 false,
             ))),
             n @ 40..=47 => Some(CellModifier::BGColor(Color(
-                unsafe { transmute(n - 40) },
+                unsafe { transmute::<u8, BaseColor>(n - 40) },
                 false,
             ))),
             n @ 90..=97 => Some(CellModifier::FGColor(Color(
-                unsafe { transmute(n - 90) },
+                unsafe { transmute::<u8, BaseColor>(n - 90) },
                 true,
             ))),
             n @ 100..=107 => Some(CellModifier::BGColor(Color(
-                unsafe { transmute(n - 100) },
+                unsafe { transmute::<u8, BaseColor>(n - 100) },
                 true,
             ))),
             _ => None,
@@ -124,14 +129,14 @@ impl CellModifier {
 
 #[derive(Debug, Clone, Default)]
 pub struct Cell {
-    c: char, // Tried 'character' but that's just long
+    c: String, // One grapheme cluster, so accents and emoji modifiers stay with what they modify
     modifiers: LinkedList<CellModifier>,
     page_end: bool,
 }
 
 impl Cell {
     pub fn clear(&mut self) {
-        self.c = '\0';
+        self.c.clear();
         self.modifiers.clear();
         self.page_end = false;
     }
@@ -139,21 +144,41 @@ impl Cell {
         if self.page_end {
             return (0, 0);
         }
-        // tabs don't exist
-        match self.c {
-            '\0' => (0, 0),
-            '\n' => (1, 0),
-            _ => (0, 1),
+        // how wide a tab is depends on where it is (see TermBuffer::wrap)
+        match self.c.as_str() {
+            "" | "\0" => (0, 0),
+            "\n" => (1, 0),
+            g => (0, grapheme_width(g)),
         }
     }
+    // Whether c is really the end of this cell's grapheme (e.g. an accent) rather than its own
+    fn joins_with(&self, c: char) -> bool {
+        let mut g = self.c.clone();
+        g.push(c);
+        self.area().1 > 0 && g.graphemes(true).count() == 1
+    }
 }
 
-impl fmt::Display for Cell {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl Cell {
+    fn is_tab(&self) -> bool {
+        self.c == "\t"
+    }
+    // Nothing, or nothing but whitespace (i.e. somewhere between words)
+    fn is_blank(&self) -> bool {
+        self.c.chars().all(|c| c.is_whitespace() || c == '\0')
+    }
+    // Shows text with this cell's modifiers in place of what's actually in it
+    fn fmt_as(&self, f: &mut fmt::Formatter, text: &str) -> fmt::Result {
         for modifier in self.modifiers.iter() {
             write!(f, "{}", modifier)?
         }
-        write!(f, "{}", self.c)
+        write!(f, "{}", text)
+    }
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_as(f, &self.c)
     }
 }
 
@@ -169,6 +194,13 @@ struct InfoStrings {
     user_info: Option<String>,
 }
 
+// What the buffer looked like at some point, so it can be made to look that way again
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    cells: Vec<Cell>, // only up to the end of the page the cursor was on
+    curr_idx: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct TermBuffer<'a> {
     cells: Vec<Cell>,
@@ -178,6 +210,7 @@ pub struct TermBuffer<'a> {
     dirty: DirtyFlags,
     opts: &'a BufOptions,
     info: InfoStrings,
+    viewing: Option<usize>, // an earlier page the reader is looking back at
 }
 
 impl<'a> TermBuffer<'a> {
@@ -190,6 +223,7 @@ impl<'a> TermBuffer<'a> {
             dirty: DirtyFlags::default(),
             opts,
             info: InfoStrings::default(),
+            viewing: None,
         };
         buf.resize();
         buf.cells.resize_with(buf.page_size(), Default::default);
@@ -224,17 +258,12 @@ impl<'a> TermBuffer<'a> {
     }
     // returns (row, column)
     pub fn get_cursor(&self) -> (usize, usize) {
-        let mut ret = (0, 0);
-        for cell in self.curr_content() {
-            let (dr, dc) = cell.area();
-            ret.0 += dr;
-            ret.1 += dc;
-            while ret.1 >= self.cols {
-                ret.1 -= self.cols;
-                ret.0 += 1;
-            }
-        }
-        ret
+        self.curr_content()
+            .iter()
+            .fold((0, 0), |(row, col), cell| {
+                let (dr, col) = self.wrap(col, cell);
+                (row + dr, col)
+            })
     }
     pub fn turn_page(&mut self) {
         if !self.dirty.page_turned {
@@ -244,8 +273,14 @@ impl<'a> TermBuffer<'a> {
     }
     pub fn write_char(&mut self, c: char) {
         self.dirty.modified = true;
-        self.get_curr_mut().c = c;
-        self.advance_idx();
+        if self.curr_idx > 0 && self.cells[self.curr_idx - 1].joins_with(c) {
+            self.cells[self.curr_idx - 1].c.push(c);
+        } else {
+            let cell = self.get_curr_mut();
+            cell.c.clear();
+            cell.c.push(c);
+            self.advance_idx();
+        }
     }
     // TODO: handle t which includes both modifiers and text?
     pub fn write_text(&mut self, t: &str) {
@@ -273,22 +308,36 @@ impl<'a> TermBuffer<'a> {
         self.resize();
         self.cells.resize_with(self.page_size(), Default::default);
         self.curr_idx = 0;
+        self.viewing = None;
     }
+    // Erases whole graphemes, so a wide character or an accented letter goes all at once
     pub fn erase_chars(&mut self, count: usize) {
-        self.dirty.modified = true;
-        let new_idx = self.curr_idx.saturating_sub(count);
-        for i in new_idx..=self.curr_idx {
-            self.cells[i].clear();
+        self.erase_back_to(self.curr_idx.saturating_sub(count));
+    }
+    // Erases a word at a time, along with any whitespace after it (like ctrl+w in a terminal)
+    pub fn erase_words(&mut self, count: usize) {
+        let mut idx = self.curr_idx;
+        for _ in 0..count {
+            while idx > 0 && self.cells[idx - 1].is_blank() {
+                idx -= 1;
+            }
+            while idx > 0 && !self.cells[idx - 1].is_blank() {
+                idx -= 1;
+            }
         }
-        self.curr_idx = new_idx;
+        self.erase_back_to(idx);
     }
-    // TODO: Make this not trash
+    // Erases rows as they're shown, so a line that wrapped goes one row at a time. The cursor's
+    // row only counts if there's something on it.
     pub fn erase_lines(&mut self, count: usize) {
-        let (orig_last_line, _) = self.get_cursor();
-        let mut last_line = orig_last_line;
-        while last_line > 0 && (orig_last_line - last_line) < count {
-            self.erase_chars(1);
-            last_line = self.get_cursor().0;
+        for _ in 0..count {
+            if self.curr_idx == 0 {
+                break;
+            }
+            if self.curr_idx == self.curr_row_start_idx() {
+                self.erase_chars(1);
+            }
+            self.erase_back_to(self.curr_row_start_idx());
         }
     }
 
@@ -298,11 +347,57 @@ impl<'a> TermBuffer<'a> {
         print!("{}", self);
         let _ = std::io::stdout().flush();
     }
-    pub fn clear_and_dump_prev_page(&mut self) {
-        //debug_assert!(self.curr_page() > 0);
-        self.curr_idx -= self.page_size();
-        self.clear_and_dump();
-        self.curr_idx += self.page_size();
+
+    // Shows the page before whichever one is being shown now. Every page written so far is still
+    // in cells (as it ended up, e.g. after any backspacing), so nothing has to be retold.
+    pub fn look_back(&mut self) -> bool {
+        if !self.can_look_back() {
+            return false;
+        }
+        self.viewing = Some(self.shown_page() - 1);
+        self.dirty.modified = true;
+        true
+    }
+    // Going forward from the page before the current one goes back to the current one
+    pub fn look_forward(&mut self) {
+        if let Some(page) = self.viewing {
+            self.viewing = (page + 1 < self.curr_page()).then_some(page + 1);
+            self.dirty.modified = true;
+        }
+    }
+    pub fn stop_looking_back(&mut self) {
+        if self.viewing.take().is_some() {
+            self.dirty.modified = true;
+        }
+    }
+    pub fn can_look_back(&self) -> bool {
+        self.shown_page() > 0
+    }
+    pub fn is_looking_back(&self) -> bool {
+        self.viewing.is_some()
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            cells: self.cells[..=self.curr_page_end_idx()].to_vec(),
+            curr_idx: self.curr_idx,
+        }
+    }
+    pub fn rewind_to(&mut self, checkpoint: Checkpoint) {
+        self.cells = checkpoint.cells;
+        self.curr_idx = checkpoint.curr_idx;
+        self.viewing = None;
+        self.dirty = DirtyFlags {
+            page_turned: false,
+            modified: true,
+        };
+    }
+    // The text on the row the cursor is on, up to the cursor
+    pub fn curr_line(&self) -> String {
+        self.cells[self.curr_row_start_idx()..self.curr_idx]
+            .iter()
+            .map(|cell| if cell.is_tab() { " " } else { cell.c.as_str() })
+            .collect()
     }
 
     pub fn set_info(&mut self, state: String, info: Option<String>) {
@@ -317,11 +412,13 @@ impl<'a> TermBuffer<'a> {
     }
 
     fn try_parse_modifier(&mut self, m: &str) -> usize {
-        let re = Regex::new(r"^\u{1b}\[((\d+;?)+)m").expect("Typo if this does not work");
+        static RE: OnceLock<Regex> = OnceLock::new();
+        let re = RE.get_or_init(|| {
+            Regex::new(r"^\u{1b}\[((\d+;?)+)m").expect("Typo if this does not work")
+        });
         re.captures(m)
             .map(|cap| {
                 cap[1]
-                    .to_string()
                     .split(';')
                     .filter_map(|d| d.parse::<u8>().ok())
                     .filter_map(CellModifier::from_val)
@@ -330,6 +427,51 @@ impl<'a> TermBuffer<'a> {
             })
             .unwrap_or(0)
     }
+    // Where the cell after one ending at col goes: (rows down, column it ends at). Wide characters
+    // that won't fit at the end of a row go on the next one instead of being cut in half
+    fn wrap(&self, col: usize, cell: &Cell) -> (usize, usize) {
+        if cell.is_tab() {
+            // Tabs go to the next tab stop, or just to the end of the row if there isn't one
+            let tab_width = self.opts.tab_width.max(1);
+            let stop = col + tab_width - col % tab_width;
+            return (0, stop.min(self.cols.max(col)));
+        }
+        match cell.area() {
+            (0, dc) if col > 0 && col + dc > self.cols => (1, dc),
+            (0, dc) => (0, col + dc),
+            (dr, _) => (dr, 0),
+        }
+    }
+    // Clears everything from new_idx up to the cursor, and moves the cursor there. Colors and
+    // text effects stay though, or else erasing the end of some red text (along with what put
+    // things back to normal after it) would leave everything after it red.
+    fn erase_back_to(&mut self, new_idx: usize) {
+        self.dirty.modified = true;
+        let mut modifiers = LinkedList::new();
+        for cell in &mut self.cells[new_idx..=self.curr_idx] {
+            modifiers.append(&mut cell.modifiers);
+            cell.clear();
+        }
+        self.cells[new_idx].modifiers = modifiers;
+        self.curr_idx = new_idx;
+    }
+    // Where the row the cursor is on starts (never before the start of the current page)
+    fn curr_row_start_idx(&self) -> usize {
+        let page_start = self.curr_page_start_idx();
+        let mut start = page_start;
+        let mut col = 0;
+        for (idx, cell) in self.cells[page_start..self.curr_idx].iter().enumerate() {
+            let (wrapped, new_col) = self.wrap(col, cell);
+            if cell.area().0 > 0 {
+                // a newline ends its row
+                start = page_start + idx + 1;
+            } else if wrapped > 0 {
+                start = page_start + idx;
+            }
+            col = new_col;
+        }
+        start
+    }
     fn advance_idx(&mut self) {
         self.curr_idx += 1;
         if self.curr_idx >= self.cells.len() {
@@ -341,6 +483,13 @@ impl<'a> TermBuffer<'a> {
     fn curr_page(&self) -> usize {
         self.curr_idx / self.page_size()
     }
+    fn shown_page(&self) -> usize {
+        self.viewing.unwrap_or_else(|| self.curr_page())
+    }
+    fn shown_content(&self) -> &[Cell] {
+        let start = self.page_size() * self.shown_page();
+        &self.cells[start..start + self.page_size()]
+    }
     fn curr_content(&self) -> &[Cell] {
         &self.cells[self.curr_page_start_idx()..=self.curr_page_end_idx()]
     }
@@ -363,24 +512,36 @@ impl<'a> fmt::Display for TermBuffer<'a> {
                 f,
                 "{}Page {}\n{}",
                 light_red,
-                self.curr_page() + 1,
+                self.shown_page() + 1,
                 TextEffect::None
             )?;
         }
 
         let mut c = 0;
-        for cell in self.curr_content() {
-            let (_, dc) = cell.area();
-            c += dc;
-            while c >= self.cols {
+        for cell in self.shown_content() {
+            let (dr, _) = cell.area();
+            let (wrapped, col) = self.wrap(c, cell);
+            if dr == 0 && wrapped > 0 {
                 writeln!(f)?;
-                c -= self.cols;
             }
-
-            write!(f, "{}", cell)?
+            // the terminal's idea of tab stops might not be ours, so tabs become spaces
+            if cell.is_tab() {
+                cell.fmt_as(f, &" ".repeat(col - c))?
+            } else {
+                write!(f, "{}", cell)?
+            }
+            c = col;
         }
 
         write!(f, "{}", light_red)?;
+        if let Some(page) = self.viewing {
+            write!(
+                f,
+                "\nLooking back at page {} of {} (b goes further back, f goes forward)",
+                page + 1,
+                self.curr_page() + 1
+            )?;
+        }
         if let Some(ref info) = self.info.user_info {
             write!(f, "\n{}", info)?;
         }
@@ -400,3 +561,160 @@ impl<'a> fmt::Display for TermBuffer<'a> {
         write!(f, "\x1b[0m")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(cols: usize) -> BufOptions {
+        BufOptions {
+            max_num_cols: Some(cols),
+            max_num_rows: Some(5),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn wide_chars_wrap() {
+        let opts = opts(10);
+        let mut buf = TermBuffer::new(&opts);
+        buf.write_text("こんにちは世界");
+        assert_eq!(buf.get_cursor(), (1, 4));
+        assert!(buf.to_string().starts_with("こんにちは\n世界"));
+    }
+
+    #[test]
+    fn wide_chars_not_cut_in_half() {
+        let opts = opts(4);
+        let mut buf = TermBuffer::new(&opts);
+        buf.write_text("abc日本");
+        assert_eq!(buf.get_cursor(), (1, 4));
+        assert!(buf.to_string().starts_with("abc\n日本"));
+    }
+
+    #[test]
+    fn erase_graphemes() {
+        let opts = opts(10);
+        let mut buf = TermBuffer::new(&opts);
+        // the accent is its own char
+        buf.write_text("cafe\u{301}");
+        assert_eq!(buf.get_cursor(), (0, 4));
+        buf.erase_chars(1);
+        assert!(buf.to_string().starts_with("caf\x1b"));
+        assert_eq!(buf.get_cursor(), (0, 3));
+
+        // thumbs up with a skin tone, then a family joined with ZWJs
+        buf.write_text("👍🏽👨\u{200d}👩\u{200d}👧");
+        assert_eq!(buf.get_cursor(), (0, 7));
+        buf.erase_chars(1);
+        assert_eq!(buf.get_cursor(), (0, 5));
+        buf.erase_chars(1);
+        assert_eq!(buf.get_cursor(), (0, 3));
+    }
+
+    #[test]
+    fn erase_words() {
+        let opts = opts(20);
+        let mut buf = TermBuffer::new(&opts);
+        buf.write_text("one two\tthree  \n");
+        buf.erase_words(1);
+        assert_eq!(buf.curr_line(), "one two ");
+        buf.erase_words(2);
+        assert_eq!(buf.get_cursor(), (0, 0));
+        // there's nothing left to erase
+        buf.erase_words(1);
+        assert_eq!(buf.get_cursor(), (0, 0));
+    }
+
+    #[test]
+    fn erasing_keeps_styles() {
+        let opts = opts(20);
+        let mut buf = TermBuffer::new(&opts);
+        buf.write_text("a ");
+        buf.write_text("\x1b[91mred");
+        buf.write_text("\x1b[0m");
+        buf.erase_words(1);
+        buf.write_text("plain");
+        assert!(buf.to_string().starts_with("a \x1b[91m\x1b[0mplain"));
+    }
+
+    #[test]
+    fn erase_lines() {
+        let opts = opts(4);
+        let mut buf = TermBuffer::new(&opts);
+        buf.write_text("ab\ncdefg\nh");
+        buf.erase_lines(1);
+        assert_eq!(buf.get_cursor(), (3, 0));
+        // "cdefg" wrapped, so it's two rows
+        buf.erase_lines(1);
+        assert_eq!(buf.get_cursor(), (1, 4));
+        assert_eq!(buf.curr_line(), "cdef");
+        buf.erase_lines(2);
+        assert_eq!(buf.get_cursor(), (0, 0));
+    }
+
+    #[test]
+    fn tab_stops() {
+        let opts = BufOptions {
+            tab_width: 4,
+            ..opts(10)
+        };
+        let mut buf = TermBuffer::new(&opts);
+        buf.write_text("a\tb\tc");
+        assert_eq!(buf.get_cursor(), (0, 9));
+        assert!(buf.to_string().starts_with("a   b   c"));
+        buf.erase_chars(1);
+        buf.erase_chars(1);
+        assert_eq!(buf.get_cursor(), (0, 5));
+
+        // no tab stop left on the row, so the tab just finishes it
+        buf.clear();
+        buf.write_text("abcdefghi\tj");
+        assert_eq!(buf.get_cursor(), (1, 1));
+        assert!(buf.to_string().starts_with("abcdefghi \nj"));
+    }
+
+    #[test]
+    fn look_back_at_earlier_pages() {
+        let opts = BufOptions {
+            max_num_rows: Some(1),
+            ..opts(4)
+        };
+        let mut buf = TermBuffer::new(&opts);
+        assert!(!buf.can_look_back());
+        buf.write_text("abcdefghij");
+        buf.erase_chars(1);
+        buf.erase_chars(1);
+        buf.erase_chars(1);
+        buf.write_text("xy");
+
+        // the page that got backspaced onto looks how it ended up
+        assert!(buf.look_back());
+        assert!(buf.to_string().starts_with("efgx"));
+        assert!(buf.look_back());
+        assert!(buf.to_string().starts_with("abcd"));
+        assert!(!buf.look_back());
+        buf.look_forward();
+        assert!(buf.to_string().starts_with("efgx"));
+        buf.look_forward();
+        assert!(!buf.is_looking_back());
+        assert!(buf.to_string().starts_with("y"));
+    }
+
+    #[test]
+    fn rewind_to_checkpoint() {
+        let opts = opts(10);
+        let mut buf = TermBuffer::new(&opts);
+        buf.write_text("Well?\nYes or no");
+        let checkpoint = buf.checkpoint();
+        assert_eq!(buf.curr_line(), "Yes or no");
+
+        buf.write_text(" (y/n) y\nabcdefghijklmnopqrstuvwxyz");
+        buf.turn_page();
+        buf.rewind_to(checkpoint);
+        assert!(buf.just_modified());
+        assert!(!buf.just_turned_page());
+        assert_eq!(buf.get_cursor(), (1, 9));
+        assert!(buf.to_string().starts_with("Well?\nYes or no\x1b"));
+    }
+}
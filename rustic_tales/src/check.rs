@@ -0,0 +1,412 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+
+use script::lint::near_misses;
+use script::token::{tokenize, Token};
+
+use crate::commands::registry::StoryCommand;
+use crate::commands::story_command::Arg;
+use crate::err::{Expected, RTError};
+use crate::options::Options;
+use crate::storyteller::builtins::{DisplayImg, EndStory, Goto, JumpToFile, Return};
+use crate::storyteller::story::{Origin, Story};
+use crate::storyteller::{Debug, StoryTeller, Unit};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Problem {
+    file: String, // not always the story being checked, since stories can include other files
+    // None when the problem is with the file as a whole
+    line: Option<usize>,
+    col: Option<usize>,
+    kind: &'static str,
+    message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    file: String,
+    problems: Vec<Problem>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+// Checks every story in paths and prints a json report. Returns true if nothing was wrong.
+pub fn check_stories(paths: &[String], opts: &Options) -> bool {
+    let reports: Vec<_> = paths.iter().map(|p| check_story(p, opts)).collect();
+    match serde_json::to_string_pretty(&reports) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Could not write report: {}", e),
+    }
+    reports.iter().all(Report::is_ok)
+}
+
+pub fn check_story(path: &str, opts: &Options) -> Report {
+    let mut report = Report {
+        file: path.to_owned(),
+        problems: Vec::new(),
+    };
+    match StoryTeller::<Debug>::new(path, opts.get_buf_opts().tab_width) {
+        Ok(teller) => {
+            let mut checker = Checker {
+                teller: &teller,
+                story: teller.get_story(),
+                opts,
+                problems: &mut report.problems,
+            };
+            checker.check_commands();
+            checker.check_variables();
+        }
+        // Bad commands stop a story from loading, but are still worth reporting one by one
+        Err(RTError::Several(errs)) => {
+            for e in errs {
+                report.problems.push(load_problem(path, e));
+            }
+        }
+        Err(e) => report.problems.push(load_problem(path, e)),
+    }
+    // Typos in special syntax don't stop a story from loading, they just turn it into text
+    if let Ok(source) = fs::read_to_string(path) {
+        for (pos, msg) in near_misses(&source) {
+            report.problems.push(Problem {
+                file: path.to_owned(),
+                line: Some(pos.line),
+                col: Some(pos.col),
+                kind: "near_miss",
+                message: msg,
+            });
+        }
+    }
+    report
+}
+
+fn load_problem(path: &str, e: RTError) -> Problem {
+    match e {
+        RTError::AtPos(loc, e) => Problem {
+            file: loc.file,
+            line: Some(loc.pos.line),
+            col: Some(loc.pos.col),
+            kind: kind_of(&e),
+            message: e.to_string(),
+        },
+        e => Problem {
+            file: path.to_owned(),
+            line: None,
+            col: None,
+            kind: "unreadable",
+            message: e.to_string(),
+        },
+    }
+}
+
+fn kind_of(e: &RTError) -> &'static str {
+    match e {
+        RTError::UnrecognizedCommand(_) => "unknown_command",
+        RTError::WrongNumArguments(..) => "wrong_num_args",
+        RTError::DurError(_) => "bad_duration",
+        RTError::ParseIntError(_) | RTError::NotANumber(_) => "bad_number",
+        RTError::NoSuchSection(_) => "missing_section",
+        RTError::IOError(_) => "missing_file",
+        RTError::IncludeCycle(_) => "include_cycle",
+        // what kind of argument it was is more useful than the fact it was an argument
+        RTError::BadArgument { expected, .. } => match expected {
+            Expected::Duration => "bad_duration",
+            Expected::Count | Expected::Number => "bad_number",
+            _ => "bad_argument",
+        },
+        _ => "bad_argument",
+    }
+}
+
+// Section indices a command might send the reader to
+fn jump_targets(story: &Story, cmd: &dyn StoryCommand) -> Vec<usize> {
+    cmd.jump_targets()
+        .into_iter()
+        .filter_map(Arg::fixed)
+        .filter_map(|sect| story.find_section(sect))
+        .collect()
+}
+
+// The variable (if any) a command stores the reader's answer (or some other value) in
+fn var_set_by(cmd: &dyn StoryCommand) -> Option<String> {
+    cmd.var_set().and_then(Arg::fixed).cloned()
+}
+
+// A goto might come back, with anything set in the section it went to
+fn set_by_goto(story: &Story, cmd: &dyn StoryCommand) -> Vec<String> {
+    if !cmd.is::<Goto>() {
+        return Vec::new();
+    }
+    jump_targets(story, cmd)
+        .into_iter()
+        .flat_map(|sect| story.get_sections()[sect].unit_idxes())
+        .filter_map(|idx| match &story.get_contents()[idx] {
+            Unit::Command(call, _) => var_set_by(&*call.cmd),
+            _ => None,
+        })
+        .collect()
+}
+
+// Nothing after one of these in a section happens
+fn stops_section(cmd: &dyn StoryCommand) -> bool {
+    cmd.is::<Return>() || cmd.is::<EndStory>()
+}
+
+// Notes that set could have been set by the time target starts, and if that's news, that
+// target needs another look
+fn reach(
+    at_start: &mut [Option<HashSet<String>>],
+    todo: &mut Vec<usize>,
+    target: usize,
+    set: &HashSet<String>,
+) {
+    let first_visit = at_start[target].is_none();
+    let known = at_start[target].get_or_insert_with(HashSet::new);
+    if first_visit || !set.is_subset(known) {
+        known.extend(set.iter().cloned());
+        todo.push(target);
+    }
+}
+
+// Variables read by a unit, including any used in a command's arguments
+fn vars_read_by(unit: &Unit) -> Vec<String> {
+    match unit {
+        // (variables with a default are fine either way)
+        Unit::Special(Token::Variable(v)) => vec![v.clone()],
+        // the variable a prompt (or set, etc) stores its answer in isn't read. incr and
+        // friends do read it, but treat unset variables as 0.
+        Unit::Command(call, _) => call
+            .cmd
+            .dynamic_args()
+            .into_iter()
+            .flat_map(tokenize)
+            .filter_map(|tkn| match tkn {
+                Token::Variable(v) => Some(v),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+struct Checker<'a> {
+    teller: &'a StoryTeller<'a, Debug>,
+    story: &'a Story,
+    opts: &'a Options,
+    problems: &'a mut Vec<Problem>,
+}
+
+impl<'a> Checker<'a> {
+    fn report(&mut self, origin: Origin, kind: &'static str, message: String) {
+        self.problems.push(Problem {
+            file: self.story.file_name(origin.file).clone(),
+            line: Some(origin.pos.line),
+            col: Some(origin.pos.col),
+            kind,
+            message,
+        });
+    }
+    fn report_err(&mut self, origin: Origin, e: RTError) {
+        self.report(origin, kind_of(&e), e.to_string());
+    }
+
+    // Loading the story already checked everything about commands that doesn't need other files
+    fn check_commands(&mut self) {
+        let story = self.story;
+        for (idx, unit) in story.get_contents().iter().enumerate() {
+            if let Unit::Command(call, _) = unit {
+                if let Err(e) = self.check_command(&*call.cmd) {
+                    self.report_err(story.get_locs()[idx], e);
+                }
+            }
+        }
+    }
+    fn check_command(&self, cmd: &dyn StoryCommand) -> crate::err::Result<()> {
+        if let Some(JumpToFile {
+            file: Arg::Fixed(file),
+            section,
+        }) = cmd.downcast_ref()
+        {
+            let other = self.story.load_neighbor(file)?;
+            if let Some(sect) = section.fixed() {
+                if other.find_section(sect).is_none() {
+                    let msg = format!("'{}' has no section named '{}'", file, sect);
+                    return Err(RTError::InvalidInput(msg));
+                }
+            }
+        }
+        if let Some(DisplayImg {
+            path: Arg::Fixed(img),
+            ..
+        }) = cmd.downcast_ref()
+        {
+            let path = Path::new(self.opts.get_story_folder()).join(img);
+            if !path.is_file() {
+                let msg = format!("could not find image '{}'", path.display());
+                return Err(RTError::InvalidInput(msg));
+            }
+        }
+        Ok(())
+    }
+
+    // Reports variables that are read before any prompt could have set them. The only ways to
+    // get from one section to another are jumps and sections that fall through.
+    fn check_variables(&mut self) {
+        let story = self.story;
+        let sections = story.get_sections();
+        if sections.is_empty() {
+            return;
+        }
+
+        // the variables that could have been set by the time each section starts
+        let mut at_start: Vec<Option<HashSet<String>>> = vec![None; sections.len()];
+        at_start[0] = Some(HashSet::new());
+        let mut todo = vec![0];
+        while let Some(sect) = todo.pop() {
+            let mut set = at_start[sect].clone().unwrap_or_default();
+            let mut goes_on = sections[sect].falls_through() && sect + 1 < sections.len();
+            for idx in sections[sect].unit_idxes() {
+                if let Unit::Command(call, _) = &story.get_contents()[idx] {
+                    let cmd = &*call.cmd;
+                    set.extend(var_set_by(cmd));
+                    for target in jump_targets(story, cmd) {
+                        reach(&mut at_start, &mut todo, target, &set);
+                    }
+                    set.extend(set_by_goto(story, cmd));
+                    if stops_section(cmd) {
+                        goes_on = false;
+                        break;
+                    }
+                }
+            }
+            if goes_on {
+                reach(&mut at_start, &mut todo, sect + 1, &set);
+            }
+        }
+
+        for (sect, set) in at_start.into_iter().enumerate() {
+            // If a section can't be reached, then nothing in it is ever read
+            let mut set = match set {
+                Some(set) => set,
+                None => continue,
+            };
+            for idx in sections[sect].unit_idxes() {
+                let unit = &story.get_contents()[idx];
+                for var in vars_read_by(unit) {
+                    if !set.contains(&var) && !self.teller.is_builtin(&var) {
+                        let msg = format!("'{}' is read before anything could have set it", var);
+                        self.report(story.get_locs()[idx], "unset_variable", msg);
+                    }
+                }
+                if let Unit::Command(call, _) = unit {
+                    let cmd = &*call.cmd;
+                    set.extend(var_set_by(cmd));
+                    set.extend(set_by_goto(story, cmd));
+                    if stops_section(cmd) {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::utils::scratch_stories;
+
+    // (kind, line) of every problem, in the order they were found
+    fn problems(report: &Report) -> Vec<(&str, Option<usize>)> {
+        report.problems.iter().map(|p| (p.kind, p.line)).collect()
+    }
+
+    #[test]
+    fn good_story() {
+        let path = scratch_stories(
+            "check_good",
+            &[(
+                "good.txt",
+                "Is your favorite color red?\n\
+                 {{ prompt_yesno : $ANSWER$ }}\n\
+                 {{ jump_if_eq : ${{ANSWER}} |,| y |,| yes |,| no }}\n\
+                 #=$ yes $=#\n\
+                 Me too, ${{USER_NAME|friend}}.\n\
+                 #=$ no $=#\n\
+                 You said ${{ANSWER}}.\n",
+            )],
+        );
+        let report = check_story(&path, &Options::default());
+        assert_eq!(problems(&report), []);
+        assert!(check_stories(&[path], &Options::default()));
+    }
+
+    #[test]
+    fn bad_commands() {
+        let path = scratch_stories(
+            "check_bad",
+            &[(
+                "bad.txt",
+                "Hello\n\
+                 {{ nope : x }}\n\
+                 {{ goto : a |,| b }}\n\
+                 {{ jump_if_eq : 1 |,| 1 |,| nowhere }}\n\
+                 {{ pause : 2 parsecs }}\n",
+            )],
+        );
+        let report = check_story(&path, &Options::default());
+        // everything wrong is reported at once, not just the first thing
+        assert_eq!(
+            problems(&report),
+            [
+                ("unknown_command", Some(2)),
+                ("wrong_num_args", Some(3)),
+                ("missing_section", Some(4)),
+                ("bad_duration", Some(5)),
+            ]
+        );
+        assert_eq!(
+            report.problems[1].message,
+            "'goto' expected 1 arguments, but got 2"
+        );
+        assert_eq!(
+            report.problems[2].message,
+            "There is no section named 'nowhere'"
+        );
+        assert!(!check_stories(&[path], &Options::default()));
+    }
+
+    #[test]
+    fn unset_variables_and_near_misses() {
+        let path = scratch_stories(
+            "check_vars",
+            &[(
+                "vars.txt",
+                "Hi ${{NAME}}\n\
+                 {{ no colon }}\n",
+            )],
+        );
+        let report = check_story(&path, &Options::default());
+        assert_eq!(
+            problems(&report),
+            [("unset_variable", Some(1)), ("near_miss", Some(2))]
+        );
+        // the json report says which file each problem is in
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["problems"][0]["file"], path.as_str());
+        assert_eq!(json["problems"][0]["col"], 4);
+    }
+
+    #[test]
+    fn missing_story() {
+        let report = check_story("/nonexistent/story.txt", &Options::default());
+        assert_eq!(problems(&report), [("unreadable", None)]);
+    }
+}
@@ -0,0 +1,83 @@
+use chrono::{Local, NaiveDateTime, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+// Where a story gets the time from. A fixed time is handy for checking what a story does at
+// 2am without having to stay up for it (and for tests).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Clock {
+    #[default]
+    System,
+    Fixed(NaiveDateTime),
+}
+
+impl Clock {
+    pub fn now(&self) -> NaiveDateTime {
+        match self {
+            Clock::System => Local::now().naive_local(),
+            Clock::Fixed(time) => *time,
+        }
+    }
+    // The built-in variables that say when it is
+    pub fn builtins(&self) -> [(&'static str, String); 4] {
+        let now = self.now();
+        [
+            ("TIME", now.format("%H:%M").to_string()),
+            ("DATE", now.format("%Y-%m-%d").to_string()),
+            ("WEEKDAY", now.format("%A").to_string()),
+            ("HOUR", now.hour().to_string()),
+        ]
+    }
+}
+
+// Whether time is in [start, end). If end comes before start, the range goes past midnight
+// (e.g. 23:00 to 01:00).
+pub fn time_between(time: NaiveTime, start: NaiveTime, end: NaiveTime) -> bool {
+    if start <= end {
+        start <= time && time < end
+    } else {
+        start <= time || time < end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+
+    fn at(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn between() {
+        assert!(time_between(at(2, 30), at(2, 0), at(3, 0)));
+        assert!(time_between(at(2, 0), at(2, 0), at(3, 0)));
+        assert!(!time_between(at(3, 0), at(2, 0), at(3, 0)));
+        assert!(!time_between(at(14, 0), at(2, 0), at(3, 0)));
+        // Past midnight
+        assert!(time_between(at(23, 30), at(23, 0), at(1, 0)));
+        assert!(time_between(at(0, 30), at(23, 0), at(1, 0)));
+        assert!(!time_between(at(1, 30), at(23, 0), at(1, 0)));
+        // An empty range is never the time
+        assert!(!time_between(at(5, 0), at(5, 0), at(5, 0)));
+    }
+
+    #[test]
+    fn fixed_builtins() {
+        let time = NaiveDate::from_ymd_opt(2021, 10, 31)
+            .unwrap()
+            .and_hms_opt(2, 5, 0)
+            .unwrap();
+        let vars = Clock::Fixed(time).builtins();
+        assert_eq!(
+            vars,
+            [
+                ("TIME", "02:05".to_owned()),
+                ("DATE", "2021-10-31".to_owned()),
+                ("WEEKDAY", "Sunday".to_owned()),
+                ("HOUR", "2".to_owned()),
+            ]
+        );
+    }
+}
@@ -8,13 +8,15 @@ use crate::err::Result;
 use crate::options::DisplayUnit;
 
 pub mod prompts;
+pub mod registry;
+pub mod story_command;
+pub mod vars;
 
-// TODO: Implement the rest of this
 pub fn backspace(count: usize, unit: DisplayUnit, buf: &mut TermBuffer) {
-    if unit.is_char() {
-        buf.erase_chars(count);
-    } else {
-        unimplemented!()
+    match unit {
+        DisplayUnit::Char => buf.erase_chars(count),
+        DisplayUnit::Word => buf.erase_words(count),
+        DisplayUnit::Line => buf.erase_lines(count),
     }
 }
 
@@ -86,22 +88,18 @@ pub fn img_to_term(path: impl AsRef<Path>, buf: &mut TermBuffer) -> Result<()> {
 }
 
 // I should probably be caching these big json lists I'm getting with every call
-pub fn get_random_phrase(kind: &str, buf: &mut TermBuffer) -> Result<()> {
+// This blocks until it hears back, so it's best done on another thread
+pub fn random_phrase(kind: &str) -> Result<Option<String>> {
     let url = format!("https://randomwordgenerator.com/json/{}s.json", kind);
     let list: serde_json::Value = serde_json::from_str(&reqwest::blocking::get(&url)?.text()?)?;
-    let list = &list["data"];
-
-    if !list.is_array() {
-        return Ok(());
-    }
-    let list = list.as_array().unwrap();
+    let list = match list["data"].as_array() {
+        Some(list) if !list.is_empty() => list,
+        _ => return Ok(None),
+    };
 
     use rand::Rng;
     let mut rng = rand::thread_rng();
     let idx = rng.gen::<usize>() % list.len();
 
-    if let Some(t) = list[idx][kind].as_str() {
-        buf.write_text(t)
-    }
-    Ok(())
+    Ok(list[idx][kind].as_str().map(str::to_owned))
 }
@@ -1,127 +1,151 @@
-use std::io::{stdin, stdout, Read, Write};
-use std::os::unix::io::AsRawFd;
-use std::time::{Duration, Instant};
-
-use crate::buffer::TermBuffer;
-use crate::err::{RTError, Result};
-use crate::utils::*;
-
-pub fn prompt_yesno(
-    def: Option<String>,
-    term_settings: Option<termios::Termios>,
-    buf: &mut TermBuffer,
-) -> String {
-    let orig_term_settings = change_term(term_settings);
-    buf.write_text(" (y/n) ");
-    buf.clear_and_dump();
-    let mut temp = String::new();
-    let _ = stdin().read_line(&mut temp);
-    change_term(orig_term_settings);
-
-    match temp.trim().to_lowercase().as_ref() {
-        "yes" | "y" | "sure" | "yeah" | "ok" | "k" | "yup" | "yy" => "y".to_owned(),
-        "no" | "n" | "nah" | "no thanks" | "nope" | "nn" => "n".to_owned(),
-        _ => def.unwrap_or_else(|| "n".to_string()),
+use std::time::Duration;
+
+// How long force_input waits before erasing a wrong character, and then how long it waits
+// before erasing each one after that
+pub const SLOW_ERASE_THRESHOLD: Duration = Duration::from_millis(1000);
+pub const FAST_ERASE_THRESHOLD: Duration = Duration::from_millis(600);
+// How long force_input leaves the finished input up before the story goes on
+pub const FORCED_INPUT_LINGER: Duration = Duration::from_millis(350);
+
+const ENTER_KEY: u8 = b'\n';
+const BACKSPACE_KEYS: [u8; 2] = [8, 127];
+
+const YES: [&str; 8] = ["yes", "y", "sure", "yeah", "ok", "k", "yup", "yy"];
+const NO: [&str; 6] = ["no", "n", "nah", "no thanks", "nope", "nn"];
+
+// What an answer to prompt_yesno means. Anything that isn't a yes or a no takes the default.
+pub fn yesno_answer(typed: &str, default: Option<&str>) -> String {
+    let typed = typed.trim().to_lowercase();
+    if YES.contains(&typed.as_str()) {
+        "y".to_owned()
+    } else if NO.contains(&typed.as_str()) {
+        "n".to_owned()
+    } else {
+        default.unwrap_or("n").to_owned()
     }
 }
 
-// This function could probably use some comments/documentation
-// TODO: Make the terminal setting stuff here less confusing
-pub fn force_input(
-    input: &str,
-    settings: Option<termios::Termios>,
-    buf: &mut TermBuffer,
-) -> Result<()> {
-    use termios::*;
-    let orig_term_settings = change_term(settings);
-
-    const SLOW_ERASE_THRESHOLD: Duration = Duration::from_millis(1000);
-    const FAST_ERASE_THRESHOLD: Duration = Duration::from_millis(600);
-
-    let is_alpha_num = |s: &str| {
-        s.chars()
-            .all(|c| char::is_ascii_alphanumeric(&c) || char::is_ascii_punctuation(&c) || c == ' ')
-    };
+// What the reader has typed so far in answer to prompt_yesno
+#[derive(Debug, Clone, Default)]
+pub struct YesNoInput {
+    pub typed: String,
+}
 
-    if !is_alpha_num(input) {
-        let msg = format!(
-            "'force_input' can only force inputs which are alphanumeric. '{}' is not alphanumeric",
-            input
-        );
-        return Err(RTError::InvalidInput(msg));
+impl YesNoInput {
+    // Letters only count if they keep spelling out an answer, so e.g. 'q' still quits
+    pub fn wants(&self, key: u8) -> bool {
+        let next = format!("{}{}", self.typed, key as char).to_lowercase();
+        let spells_answer = YES.iter().chain(&NO).any(|word| word.starts_with(&next));
+        spells_answer || key == ENTER_KEY || BACKSPACE_KEYS.contains(&key)
     }
-
-    let stdin_fd = stdin().as_raw_fd();
-    let orig_termios = Termios::from_fd(stdin_fd)?;
-
-    let mut new_termios = orig_termios;
-    new_termios.c_lflag &= !(ICANON | ECHO);
-    new_termios.c_cc[VMIN] = 0;
-    new_termios.c_cc[VTIME] = 0;
-    tcsetattr(stdin_fd, TCSANOW, &new_termios)?;
-
-    let mut user_str = String::new();
-    let mut last_erase_time = Instant::now();
-    let mut erase_threshold = SLOW_ERASE_THRESHOLD;
-
-    buf.clear_and_dump();
-    while user_str != input {
-        let now = Instant::now();
-        if now.duration_since(last_erase_time) > erase_threshold
-            && !user_str.is_empty()
-            && !input.starts_with(&user_str)
-        {
-            last_erase_time = now;
-            erase_threshold = FAST_ERASE_THRESHOLD;
-
-            buf.erase_chars(1);
-            user_str.pop();
-        } else if input.starts_with(&user_str) {
-            last_erase_time = now;
+    // Returns the answer once the reader presses enter
+    pub fn key_pressed(&mut self, key: u8, default: Option<&str>) -> Option<String> {
+        if key == ENTER_KEY {
+            return Some(yesno_answer(&self.typed, default));
+        } else if BACKSPACE_KEYS.contains(&key) {
+            self.typed.pop();
+        } else if self.wants(key) {
+            self.typed.push(key as char);
         }
+        None
+    }
+    // What's shown below the story while it waits for an answer
+    pub fn show(&self, default: Option<&str>) -> String {
+        format!(
+            "Yes or no? (enter on its own means {})\n> {}",
+            default.unwrap_or("n"),
+            self.typed
+        )
+    }
+}
 
-        let new_stuff = String::from_utf8(stdin().bytes().filter_map(|res| res.ok()).collect())
-            .map_err(|_| {
-                RTError::InvalidInput("Could not understand key strokes for some reason".to_owned())
-            })?;
-        if is_alpha_num(&new_stuff) {
-            erase_threshold = SLOW_ERASE_THRESHOLD;
+// Only plain characters can be typed one key at a time
+pub fn is_forceable(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c.is_ascii_punctuation() || c == ' '
+}
 
-            user_str += &new_stuff;
-            //print!("{}", new_stuff);
-            buf.write_text(&new_stuff);
-            let _ = stdout().flush();
-        }
+// What the reader has typed so far in a choice_menu
+#[derive(Debug, Clone, Default)]
+pub struct MenuInput {
+    pub typed: String,
+    pub problem: Option<String>, // why the last thing they entered wasn't a choice
+}
 
-        if buf.just_modified() {
-            buf.clear_and_dump();
+impl MenuInput {
+    // Whether the key means something to the menu (as opposed to e.g. pausing the story)
+    pub fn wants(key: u8) -> bool {
+        key.is_ascii_digit() || key == ENTER_KEY || BACKSPACE_KEYS.contains(&key)
+    }
+    // Returns the index of the choice once one has been made
+    pub fn key_pressed(&mut self, key: u8, num_choices: usize) -> Option<usize> {
+        if key.is_ascii_digit() {
+            self.typed.push(key as char);
+        } else if BACKSPACE_KEYS.contains(&key) {
+            self.typed.pop();
+        } else if key == ENTER_KEY {
+            match self.typed.parse::<usize>() {
+                Ok(choice) if (1..=num_choices).contains(&choice) => return Some(choice - 1),
+                _ => {
+                    let msg = format!("Need to make a choice in range 1 -- {}", num_choices);
+                    self.problem = Some(msg);
+                    self.typed.clear();
+                }
+            }
         }
+        None
+    }
+    // The menu as it's shown below the story
+    pub fn show(&self, choices: &[String]) -> String {
+        let mut lines: Vec<_> = choices
+            .iter()
+            .enumerate()
+            .map(|(num, choice)| format!("{}. {}", num + 1, choice))
+            .collect();
+        if let Some(problem) = &self.problem {
+            lines.push(problem.clone());
+        }
+        lines.push(format!("> {}", self.typed));
+        lines.join("\n")
     }
-    std::thread::sleep(Duration::from_millis(350));
-    tcsetattr(stdin_fd, TCSANOW, &orig_termios)?;
-
-    change_term(orig_term_settings);
-    Ok(())
 }
 
-pub fn choice_menu(
-    choices: &[impl AsRef<str>],
-    settings: Option<termios::Termios>,
-    buf: &mut TermBuffer,
-) -> Result<String> {
-    let orig = change_term(settings);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    buf.write_char('\n');
-    buf.clear_and_dump();
-    let mut choice = menu(choices, None, false);
-    while choice.is_err() {
-        //TermAction::EraseLines(choices.len() + 2).execute();
-        buf.erase_lines(choices.len() + 2);
-        buf.clear_and_dump();
-        choice = menu(choices, None, false);
+    fn type_answer(keys: &str, default: Option<&str>) -> Option<String> {
+        let mut input = YesNoInput::default();
+        keys.bytes()
+            .find_map(|k| input.wants(k).then(|| input.key_pressed(k, default))?)
     }
-    buf.erase_lines(choices.len() + 2);
 
-    change_term(orig);
-    Ok(choices[choice.unwrap()].as_ref().to_owned())
+    #[test]
+    fn yesno_words() {
+        assert_eq!(yesno_answer("sure", None), "y");
+        assert_eq!(yesno_answer(" Yup ", None), "y");
+        assert_eq!(yesno_answer("nah", Some("y")), "n");
+        assert_eq!(yesno_answer("no thanks", Some("y")), "n");
+        // Anything else is the default, or no if there isn't one
+        assert_eq!(yesno_answer("", Some("y")), "y");
+        assert_eq!(yesno_answer("maybe", None), "n");
+    }
+
+    #[test]
+    fn typing_yesno() {
+        assert_eq!(type_answer("sure\n", None).as_deref(), Some("y"));
+        assert_eq!(type_answer("NOPE\n", Some("y")).as_deref(), Some("n"));
+        assert_eq!(type_answer("\n", Some("y")).as_deref(), Some("y"));
+        // Nothing is answered until enter
+        assert_eq!(type_answer("yes", None), None);
+        // Backspace takes back a letter
+        assert_eq!(type_answer("nn\x7f\x7fy\n", None).as_deref(), Some("y"));
+
+        // Keys that don't spell out an answer are left for pausing, quitting etc.
+        let input = YesNoInput::default();
+        assert!(!input.wants(b'p') && !input.wants(b'q') && !input.wants(b'r'));
+        let input = YesNoInput {
+            typed: "no".to_owned(),
+        };
+        assert!(input.wants(b'p') && input.wants(b' ') && !input.wants(b'q'));
+    }
 }
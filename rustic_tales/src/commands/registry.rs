@@ -0,0 +1,296 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, OnceLock, PoisonError, RwLock};
+
+use crate::buffer::TermBuffer;
+use crate::err::{RTError, Result};
+use crate::storyteller::builtins::register_builtins;
+use crate::storyteller::{StoryTeller, Telling, Transition};
+
+use super::story_command::Arg;
+
+// How many arguments a command takes. A max of None means it does not care about extra ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arity {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl Arity {
+    pub const fn exactly(num: usize) -> Arity {
+        Arity::between(num, num)
+    }
+    pub const fn between(min: usize, max: usize) -> Arity {
+        Arity {
+            min,
+            max: Some(max),
+        }
+    }
+    pub const fn at_least(min: usize) -> Arity {
+        Arity { min, max: None }
+    }
+    pub fn check(&self, func: &'static str, num_args: usize) -> Result<()> {
+        if num_args < self.min || self.max.is_some_and(|max| num_args > max) {
+            Err(RTError::WrongNumArguments(func, *self, num_args))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// How the range is described in errors, e.g. "1 or 2"
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.max {
+            None if self.min == 0 => write!(f, "any number of"),
+            None => write!(f, "at least {}", self.min),
+            Some(max) if max == self.min => write!(f, "{}", max),
+            Some(max) if max == self.min + 1 => write!(f, "{} or {}", self.min, max),
+            Some(max) => write!(f, "{} to {}", self.min, max),
+        }
+    }
+}
+
+// A command as a story wrote it ({{ name : args }}), with its arguments already made sense
+// of. Each built-in command is its own type (see storyteller/builtins.rs), and more can be added
+// with register.
+pub trait StoryCommand: Any + fmt::Debug + Send + Sync {
+    // Makes sense of the arguments when the story loads (after they've been counted), so mistakes
+    // turn up before anyone reads it. func is the name the command was written with.
+    fn parse(func: &'static str, args: &[String]) -> Result<Self>
+    where
+        Self: Sized;
+    // What happens when the story gets to the command. Anything it keeps doing after this returns
+    // (e.g. waiting for the reader) is the transition, so it can be paused, quit, etc.
+    fn run(&self, st: &mut StoryTeller<Telling>, buf: &mut TermBuffer) -> Result<Transition>;
+
+    // Every argument that uses variables, except the one (if any) a value gets stored in
+    fn dynamic_args(&self) -> Vec<&str> {
+        Vec::new()
+    }
+    // The variable (if any) this command stores the reader's answer (or some other value) in
+    fn var_set(&self) -> Option<&Arg<String>> {
+        None
+    }
+    // Sections this command might send the reader to (in the same file)
+    fn jump_targets(&self) -> Vec<&Arg<String>> {
+        Vec::new()
+    }
+}
+
+impl dyn StoryCommand {
+    // For the few places that care which command this is (e.g. check and graph)
+    pub fn downcast_ref<T: StoryCommand>(&self) -> Option<&T> {
+        (self as &dyn Any).downcast_ref()
+    }
+    pub fn is<T: StoryCommand>(&self) -> bool {
+        self.downcast_ref::<T>().is_some()
+    }
+}
+
+type Parse = fn(&'static str, &[String]) -> Result<Arc<dyn StoryCommand>>;
+
+fn parse_as<T: StoryCommand>(func: &'static str, args: &[String]) -> Result<Arc<dyn StoryCommand>> {
+    Ok(Arc::new(T::parse(func, args)?))
+}
+
+// What a story can call, and how many arguments it takes
+#[derive(Clone, Copy)]
+struct Definition {
+    name: &'static str,
+    arity: Arity,
+    parse: Parse,
+}
+
+// A command as it's written in a story: what it was called, and what it was given
+#[derive(Clone)]
+pub struct Call {
+    pub name: &'static str,
+    pub cmd: Arc<dyn StoryCommand>,
+}
+
+impl Call {
+    pub fn run(&self, st: &mut StoryTeller<Telling>, buf: &mut TermBuffer) -> Result<Transition> {
+        self.cmd.run(st, buf)
+    }
+}
+
+impl fmt::Debug for Call {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {:?}", self.name, self.cmd)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Registry {
+    commands: HashMap<&'static str, Definition>,
+}
+
+impl Registry {
+    pub fn with_builtins() -> Registry {
+        let mut registry = Registry::default();
+        register_builtins(&mut registry);
+        registry
+    }
+    // Stories can call name, and T makes sense of what it's given. Replaces whatever command
+    // already had the same name.
+    pub fn register<T: StoryCommand>(&mut self, name: &'static str, arity: Arity) {
+        let parse = parse_as::<T>;
+        self.commands
+            .insert(name, Definition { name, arity, parse });
+    }
+    // Makes sure func is a real command and that it was given arguments it can use
+    pub fn parse(&self, func: &str, args: &[String]) -> Result<Call> {
+        let def = self
+            .commands
+            .get(func)
+            .ok_or_else(|| RTError::UnrecognizedCommand(func.to_owned()))?;
+        def.arity.check(def.name, args.len())?;
+        Ok(Call {
+            name: def.name,
+            cmd: (def.parse)(def.name, args)?,
+        })
+    }
+}
+
+// The commands stories get loaded with
+fn registry() -> &'static RwLock<Registry> {
+    static REGISTRY: OnceLock<RwLock<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(Registry::with_builtins()))
+}
+
+// Lets stories call T by name. This only affects stories loaded after it's called.
+pub fn register<T: StoryCommand>(name: &'static str, arity: Arity) {
+    registry()
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .register::<T>(name, arity);
+}
+
+pub fn parse(func: &str, args: &[String]) -> Result<Call> {
+    registry()
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .parse(func, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::commands::story_command::parse_arg;
+    use crate::err::Expected;
+    use crate::storyteller::builtins::{Add, Goto};
+
+    // Says its arguments louder
+    #[derive(Debug)]
+    struct Shout(Vec<Arg<String>>);
+
+    impl StoryCommand for Shout {
+        fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+            let args = (0..args.len()).map(|i| parse_arg(func, args, i));
+            Ok(Shout(args.collect::<Result<_>>()?))
+        }
+        fn run(&self, st: &mut StoryTeller<Telling>, buf: &mut TermBuffer) -> Result<Transition> {
+            for arg in &self.0 {
+                buf.write_text(&st.eval(arg)?.to_uppercase());
+            }
+            Ok(Transition::Nothing)
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|&arg| arg.to_owned()).collect()
+    }
+
+    #[test]
+    fn describe_arity() {
+        assert_eq!(Arity::exactly(1).to_string(), "1");
+        assert_eq!(Arity::between(1, 2).to_string(), "1 or 2");
+        assert_eq!(Arity::between(2, 4).to_string(), "2 to 4");
+        assert_eq!(Arity::at_least(2).to_string(), "at least 2");
+        assert_eq!(Arity::at_least(0).to_string(), "any number of");
+    }
+
+    #[test]
+    fn builtins() {
+        let registry = Registry::with_builtins();
+        let call = registry.parse("goto", &args(&["end"])).unwrap();
+        let goto = call.cmd.downcast_ref::<Goto>().unwrap();
+        assert_eq!(goto.0, Arg::Fixed("end".to_owned()));
+        // incr is just add with an amount already filled in
+        let call = registry.parse("incr", &args(&["x"])).unwrap();
+        assert_eq!(call.name, "incr");
+        assert!(call.cmd.is::<Add>());
+
+        assert!(matches!(
+            registry.parse("goto", &args(&["a", "b"])),
+            Err(RTError::WrongNumArguments("goto", _, 2))
+        ));
+        assert!(matches!(
+            registry.parse("shout", &args(&["hi"])),
+            Err(RTError::UnrecognizedCommand(_))
+        ));
+    }
+
+    // Which argument was bad, and what it should have been
+    fn bad_arg(func: &str, args_given: &[&str]) -> Option<(usize, Expected, String)> {
+        match Registry::with_builtins().parse(func, &args(args_given)) {
+            Err(RTError::BadArgument {
+                idx, expected, got, ..
+            }) => Some((idx, expected, got)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn bad_arguments() {
+        let bad = |idx, expected, got: &str| Some((idx, expected, got.to_owned()));
+        let yes_no = bad(1, Expected::YesOrNo, "maybe");
+        assert_eq!(bad_arg("fallthrough", &["maybe"]), yes_no);
+        // fallthrough is needed before anyone reads the story, so it can't wait for variables
+        let fixed = bad(1, Expected::Fixed, "${{X}}");
+        assert_eq!(bad_arg("fallthrough", &["${{X}}"]), fixed);
+        let time = bad(2, Expected::Time, "noon");
+        assert_eq!(
+            bad_arg("jump_if_time_between", &["9:00", "noon", "a"]),
+            time
+        );
+        let weight = bad(2, Expected::Weight, "0");
+        assert_eq!(bad_arg("jump_random", &["a", "0", "b", "0"]), weight);
+        let unit = bad(2, Expected::DisplayUnit, "pages");
+        assert_eq!(bad_arg("backspace", &["2", "pages"]), unit);
+        let count = bad(1, Expected::Count, "-2");
+        assert_eq!(bad_arg("backspace", &["-2", "chars"]), count);
+        let forceable = bad(1, Expected::Forceable, "tab\there");
+        assert_eq!(bad_arg("force_input", &["tab\there"]), forceable);
+        let number = bad(2, Expected::Number, "ten");
+        assert_eq!(bad_arg("jump_if_lt", &["9", "ten", "a"]), number);
+        let text = bad(1, Expected::Text, "#=$ a $=#");
+        assert_eq!(bad_arg("goto", &["#=$ a $=#"]), text);
+        assert_eq!(bad_arg("jump_random", &["a", "0", "b", "1"]), None);
+    }
+
+    #[test]
+    fn custom_commands() {
+        let mut registry = Registry::with_builtins();
+        registry.register::<Shout>("shout", Arity::at_least(1));
+        let given = args(&["hi", "${{NAME}}"]);
+        let call = registry.parse("shout", &given).unwrap();
+        assert_eq!(call.name, "shout");
+        let shout = call.cmd.downcast_ref::<Shout>().unwrap();
+        let dynamic = Arg::Dynamic {
+            arg: "${{NAME}}".to_owned(),
+            func: "shout",
+            idx: 2,
+        };
+        assert_eq!(shout.0, vec![Arg::Fixed("hi".to_owned()), dynamic]);
+        // Arguments get counted the same way they are for built-ins
+        let err = registry.parse("shout", &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "'shout' expected at least 1 arguments, but got 0"
+        );
+    }
+}
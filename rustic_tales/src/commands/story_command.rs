@@ -0,0 +1,189 @@
+use std::time::Duration;
+
+use chrono::NaiveTime;
+use humantime::parse_duration;
+
+use script::token::{tokenize, Token};
+
+use crate::err::{Expected, RTError, Result};
+use crate::options::DisplayUnit;
+
+use super::prompts::is_forceable;
+use super::vars::{as_number, interpolate};
+
+// Anything a command's argument can be turned into
+pub trait ArgType: Sized + Clone {
+    // What the argument should look like, for when it doesn't
+    const EXPECTED: Expected;
+    fn from_arg(arg: &str) -> Option<Self>;
+}
+
+impl ArgType for String {
+    const EXPECTED: Expected = Expected::Text;
+    fn from_arg(arg: &str) -> Option<Self> {
+        Some(arg.to_owned())
+    }
+}
+
+impl ArgType for usize {
+    const EXPECTED: Expected = Expected::Count;
+    fn from_arg(arg: &str) -> Option<Self> {
+        arg.parse().ok()
+    }
+}
+
+impl ArgType for isize {
+    const EXPECTED: Expected = Expected::Number;
+    fn from_arg(arg: &str) -> Option<Self> {
+        arg.parse().ok()
+    }
+}
+
+impl ArgType for i64 {
+    const EXPECTED: Expected = Expected::Number;
+    fn from_arg(arg: &str) -> Option<Self> {
+        as_number(arg).ok()
+    }
+}
+
+impl ArgType for Duration {
+    const EXPECTED: Expected = Expected::Duration;
+    fn from_arg(arg: &str) -> Option<Self> {
+        parse_duration(arg).ok()
+    }
+}
+
+impl ArgType for bool {
+    const EXPECTED: Expected = Expected::YesOrNo;
+    fn from_arg(arg: &str) -> Option<Self> {
+        match arg.to_ascii_lowercase().as_str() {
+            "yes" | "true" => Some(true),
+            "no" | "false" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+impl ArgType for NaiveTime {
+    const EXPECTED: Expected = Expected::Time;
+    fn from_arg(arg: &str) -> Option<Self> {
+        NaiveTime::parse_from_str(arg, "%H:%M")
+            .or_else(|_| NaiveTime::parse_from_str(arg, "%H:%M:%S"))
+            .ok()
+    }
+}
+
+impl ArgType for DisplayUnit {
+    const EXPECTED: Expected = Expected::DisplayUnit;
+    fn from_arg(arg: &str) -> Option<Self> {
+        match arg.to_ascii_lowercase().as_str() {
+            "chars" | "char" => Some(DisplayUnit::Char),
+            "words" | "word" => Some(DisplayUnit::Word),
+            "lines" | "line" => Some(DisplayUnit::Line),
+            _ => None,
+        }
+    }
+}
+
+// Text that force_input can make the reader type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Forceable(pub String);
+
+impl ArgType for Forceable {
+    const EXPECTED: Expected = Expected::Forceable;
+    fn from_arg(arg: &str) -> Option<Self> {
+        arg.chars()
+            .all(is_forceable)
+            .then(|| Forceable(arg.to_owned()))
+    }
+}
+
+// Arguments that depend on variables can only be worked out when the story is being told
+pub fn is_dynamic(arg: &str) -> bool {
+    tokenize(arg)
+        .iter()
+        .any(|tkn| matches!(tkn, Token::Variable(_) | Token::VariableOr(..)))
+}
+
+pub fn bad_arg(func: &'static str, idx: usize, expected: Expected, got: &str) -> RTError {
+    RTError::BadArgument {
+        func,
+        idx,
+        expected,
+        got: got.to_owned(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Arg<T> {
+    Fixed(T),
+    // The argument as written, interpolated and parsed when the command runs. Which argument
+    // of which command it was is kept in case it turns out to be bad then.
+    Dynamic {
+        arg: String,
+        func: &'static str,
+        idx: usize,
+    },
+}
+
+impl<T: ArgType> Arg<T> {
+    // idx is which argument of func this is, counting from 1
+    fn parse(func: &'static str, idx: usize, arg: &str) -> Result<Arg<T>> {
+        // Even if it uses variables, there's no reason to wait to find out arg is nonsense
+        let text =
+            interpolate(arg, |_| None).ok_or_else(|| bad_arg(func, idx, Expected::Text, arg))?;
+        if is_dynamic(arg) {
+            let arg = arg.to_owned();
+            Ok(Arg::Dynamic { arg, func, idx })
+        } else {
+            T::from_arg(&text)
+                .map(Arg::Fixed)
+                .ok_or_else(|| bad_arg(func, idx, T::EXPECTED, &text))
+        }
+    }
+    // lookup gives the values of variables (see interpolate)
+    pub fn eval(&self, lookup: impl Fn(&str) -> Option<String>) -> Result<T> {
+        match self {
+            Arg::Fixed(t) => Ok(t.clone()),
+            Arg::Dynamic { arg, func, idx } => {
+                let text = interpolate(arg, lookup)
+                    .ok_or_else(|| bad_arg(func, *idx, Expected::Text, arg))?;
+                T::from_arg(&text).ok_or_else(|| bad_arg(func, *idx, T::EXPECTED, &text))
+            }
+        }
+    }
+    pub fn fixed(&self) -> Option<&T> {
+        match self {
+            Arg::Fixed(t) => Some(t),
+            Arg::Dynamic { .. } => None,
+        }
+    }
+    pub fn dynamic(&self) -> Option<&str> {
+        match self {
+            Arg::Fixed(_) => None,
+            Arg::Dynamic { arg, .. } => Some(arg),
+        }
+    }
+}
+
+// The i-th argument (counting from 0) of func, saying which one it was if it's bad so the author
+// doesn't have to guess
+pub fn parse_arg<T: ArgType>(func: &'static str, args: &[String], i: usize) -> Result<Arg<T>> {
+    Arg::parse(func, i + 1, &args[i])
+}
+
+pub fn parse_opt_arg<T: ArgType>(
+    func: &'static str,
+    args: &[String],
+    i: usize,
+) -> Result<Option<Arg<T>>> {
+    args.get(i).map(|_| parse_arg(func, args, i)).transpose()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+}
@@ -0,0 +1,111 @@
+use std::cmp::Ordering;
+
+use script::token::{tokenize, Token};
+
+use crate::err::{RTError, Result};
+
+// What a variable stands for, given lookup (see interpolate). An unset variable is empty unless
+// it has a default (e.g. ${{NAME|stranger}}), and check warns about the ones that don't.
+pub fn var_value(tkn: &Token, lookup: impl Fn(&str) -> Option<String>) -> String {
+    match tkn {
+        Token::Variable(v) => lookup(v).unwrap_or_default(),
+        Token::VariableOr(v, def) => lookup(v).unwrap_or_else(|| def.clone()),
+        _ => String::new(),
+    }
+}
+
+// Expands everything in a command's argument (text, chars and variables) into a single string.
+// lookup gives the value of a variable, or None if it is unset. Arguments with anything else in
+// them (e.g. a section header) make no sense, and give None.
+pub fn interpolate(arg: &str, lookup: impl Fn(&str) -> Option<String>) -> Option<String> {
+    use Token::*;
+    let tkns = tokenize(arg);
+    // A lone symbol is how prompts are told which variable to store their answer in
+    if let [Symbol(s)] = tkns.as_slice() {
+        return Some(s.clone());
+    }
+    let mut ret = String::new();
+    for tkn in tkns {
+        match tkn {
+            Text(s) => ret += &s,
+            Symbol(s) => ret += &format!("${}$", s),
+            Char(c) => ret.push(c),
+            Variable(_) | VariableOr(..) => ret += &var_value(&tkn, &lookup),
+            Comment(_) => {}
+            _ => return None,
+        }
+    }
+    Some(ret)
+}
+
+// Variables are stored as text, but anything that looks like an integer can be used as one
+pub fn as_number(val: &str) -> Result<i64> {
+    val.trim()
+        .parse()
+        .map_err(|_| RTError::NotANumber(val.to_owned()))
+}
+
+// Only numbers can be less or greater than each other
+pub fn compare_numbers(lhs: &str, rhs: &str) -> Result<Ordering> {
+    Ok(as_number(lhs)?.cmp(&as_number(rhs)?))
+}
+
+// The new value of var after adding amount to it. Unset variables count as 0 so stories
+// can start counting without having to set things up first.
+pub fn add_to(var: &str, val: Option<&str>, amount: i64) -> Result<i64> {
+    let val = val.map(as_number).transpose()?.unwrap_or(0);
+    val.checked_add(amount)
+        .ok_or_else(|| RTError::Overflow(var.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(var: &str) -> Option<String> {
+        match var {
+            "NAME" => Some("Ada".to_owned()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn interpolating() {
+        assert_eq!(interpolate("plain text", lookup).unwrap(), "plain text");
+        assert_eq!(interpolate("I am ${{NAME}}", lookup).unwrap(), "I am Ada");
+        assert_eq!(interpolate("${{NAME|stranger}}", lookup).unwrap(), "Ada");
+        // A default is only used if the variable was never set
+        assert_eq!(interpolate("[${{EMPTY|none}}]", lookup).unwrap(), "[]");
+        assert_eq!(interpolate("${{UNSET|anon}}", lookup).unwrap(), "anon");
+        assert_eq!(interpolate("[${{UNSET}}]", lookup).unwrap(), "[]");
+        // A lone symbol is a variable name, but one mixed with text is printed as written
+        assert_eq!(interpolate("$NAME$", lookup).unwrap(), "NAME");
+        assert_eq!(interpolate("a $NAME$", lookup).unwrap(), "a $NAME$");
+        assert_eq!(interpolate("#=$ sect $=#", lookup), None);
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(as_number("42").unwrap(), 42);
+        assert_eq!(as_number(" -7 ").unwrap(), -7);
+        assert!(matches!(as_number("4.5"), Err(RTError::NotANumber(v)) if v == "4.5"));
+        assert!(matches!(as_number(""), Err(RTError::NotANumber(_))));
+
+        assert_eq!(compare_numbers("9", "10").unwrap(), Ordering::Less);
+        assert_eq!(compare_numbers("05", "5").unwrap(), Ordering::Equal);
+        assert!(matches!(compare_numbers("9", "ten"), Err(RTError::NotANumber(v)) if v == "ten"));
+    }
+
+    #[test]
+    fn adding() {
+        assert_eq!(add_to("X", Some("2"), 3).unwrap(), 5);
+        assert_eq!(add_to("X", Some("2"), -3).unwrap(), -1);
+        // Unset variables start from 0
+        assert_eq!(add_to("X", None, 1).unwrap(), 1);
+        let err = add_to("X", Some("two"), 1).unwrap_err();
+        assert!(matches!(err, RTError::NotANumber(_)));
+        let max = i64::MAX.to_string();
+        assert!(matches!(add_to("X", Some(&max), 1), Err(RTError::Overflow(v)) if v == "X"));
+    }
+}
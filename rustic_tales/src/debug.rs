@@ -3,6 +3,7 @@ use std::{thread::sleep, time::Duration};
 
 use crate::buffer::*;
 use crate::err::Result;
+use crate::graph::StoryGraph;
 use crate::options::Options;
 use crate::storyteller::story::{Line, Page};
 use crate::storyteller::{Debug, StoryTeller};
@@ -10,12 +11,18 @@ use crate::utils::*;
 
 pub fn debug_menu(opts: &Options) -> Result<bool> {
     let mut should_wait = true;
-    let debug_fns = [tokenize_story, parse_story, get_pagination_info];
+    let debug_fns = [
+        tokenize_story,
+        parse_story,
+        get_pagination_info,
+        show_section_graph,
+    ];
     match menu(
         &[
             "Tokenize Story",
             "Separate Story into Units",
             "Pagination Info for Story",
+            "Section Graph for Story",
             "Print Some Constants",
             "Test the buffer stuff",
         ],
@@ -25,7 +32,7 @@ pub fn debug_menu(opts: &Options) -> Result<bool> {
         Err(e) => println!("Something went wrong: '{}'", e),
         Ok(n) if (0..debug_fns.len()).contains(&n) => {
             match choose_story(opts.get_ignored(), opts.get_story_folder()) {
-                Ok(story) => match StoryTeller::new(&story) {
+                Ok(story) => match StoryTeller::new(&story, opts.get_buf_opts().tab_width) {
                     Ok(st) => {
                         should_wait = false;
                         debug_fns[n](story, st)
@@ -36,8 +43,8 @@ pub fn debug_menu(opts: &Options) -> Result<bool> {
                 Err(e) => println!("Something went wrong: '{}'", e),
             }
         }
-        Ok(3) => print_some_constants(opts),
-        Ok(4) => run_buffer_tests(opts),
+        Ok(4) => print_some_constants(opts),
+        Ok(5) => run_buffer_tests(opts),
         Ok(_) => unreachable!("Menu only returns valid choices"),
     }
     Ok(!should_wait)
@@ -53,8 +60,8 @@ fn tokenize_story(story: String, _teller: StoryTeller<Debug>) {
     for chunk in tkns.chunks(chunk_size) {
         wait_for_kb();
         clear_screen();
-        for tkn in chunk {
-            println!("{:?}", tkn);
+        for (tkn, pos) in chunk {
+            println!("{}:{}\t{:?}", pos.line, pos.col, tkn);
         }
     }
     wait_for_enter("That's all... (hit enter)");
@@ -112,6 +119,14 @@ fn get_pagination_info(story: String, teller: StoryTeller<Debug>) {
     wait_for_enter("Press enter to continue...");
 }
 
+fn show_section_graph(_story: String, teller: StoryTeller<Debug>) {
+    let graph = StoryGraph::new(teller.get_story());
+    println!("{}\n", graph.summary());
+    println!("In DOT (try pasting it into a Graphviz viewer):\n");
+    print!("{}", graph.to_dot());
+    wait_for_enter("Press enter to continue...");
+}
+
 fn print_some_constants(opts: &Options) {
     println!("max_line_length: {}", Line::max_line_len());
     println!("max_page_height: {}", Page::max_page_height());
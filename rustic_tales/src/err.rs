@@ -1,7 +1,53 @@
 use std::fmt;
 
+use script::token::Pos;
+
+use crate::commands::registry::Arity;
+
 pub type Result<T> = std::result::Result<T, RTError>;
 
+// Where in a story file something went wrong
+#[derive(Debug)]
+pub struct SourceLoc {
+    pub file: String,
+    pub pos: Pos,
+    pub line: String, // the offending line, so the error can show it
+}
+
+// What a command's argument should have looked like
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expected {
+    Text, // as opposed to e.g. a section header
+    Count,
+    Number,
+    Duration,
+    YesOrNo,
+    Time,
+    DisplayUnit,
+    Forceable, // something force_input can make the reader type
+    Weight,    // jump_random needs at least one that isn't 0
+    Fixed,     // no variables, since it's needed when the story loads
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Expected::*;
+        let expected = match self {
+            Text => "just text, chars and variables",
+            Count => "a count like 3",
+            Number => "a whole number like -3",
+            Duration => "a length of time like 2s or 250ms",
+            YesOrNo => "yes or no",
+            Time => "a time like 14:30",
+            DisplayUnit => "chars, words or lines",
+            Forceable => "just letters, numbers, punctuation and spaces",
+            Weight => "a weight, and they can't all be 0",
+            Fixed => "something without variables, since it's needed when the story loads",
+        };
+        write!(f, "{}", expected)
+    }
+}
+
 #[derive(Debug)]
 pub enum RTError {
     IOError(std::io::Error),
@@ -15,11 +61,26 @@ pub enum RTError {
 
     InvalidInput(String),
     UnrecognizedCommand(String),
-    WrongNumArguments(&'static str, &'static str, usize),
+    WrongNumArguments(&'static str, Arity, usize),
     #[allow(dead_code)]
     NotYetImplemented(String),
     #[allow(dead_code)]
     Internal(&'static str),
+
+    IncludeCycle(Vec<String>),
+    NotANumber(String),
+    Overflow(String),
+    NoSuchSection(String),
+    StoryChanged(String),
+    BadArgument {
+        func: &'static str,
+        idx: usize, // which argument, counting from 1
+        expected: Expected,
+        got: String,
+    },
+    NothingToReturnTo,
+    Several(Vec<RTError>),
+    AtPos(SourceLoc, Box<RTError>),
 }
 
 impl fmt::Display for RTError {
@@ -41,6 +102,40 @@ impl fmt::Display for RTError {
             }
             NotYetImplemented(r) => write!(f, "{} is not yet implemented", r),
             Internal(e) => write!(f, "Internal error: {}", e),
+            IncludeCycle(files) => {
+                write!(f, "Files include each other: {}", files.join(" -> "))
+            }
+            NotANumber(val) => write!(f, "'{}' is not a number", val),
+            Overflow(var) => write!(f, "'{}' got too big (or too small) to store", var),
+            NoSuchSection(sect) => write!(f, "There is no section named '{}'", sect),
+            StoryChanged(story) => write!(
+                f,
+                "'{}' has changed since it was saved, so the save no longer lines up with it",
+                story
+            ),
+            BadArgument {
+                func,
+                idx,
+                expected,
+                got,
+            } => write!(
+                f,
+                "Argument {} of '{}' should be {}, not '{}'",
+                idx, func, expected, got
+            ),
+            NothingToReturnTo => write!(f, "There's no goto to return to"),
+            Several(errs) => {
+                let errs: Vec<_> = errs.iter().map(ToString::to_string).collect();
+                write!(f, "{}", errs.join("\n\n"))
+            }
+            AtPos(loc, e) => {
+                let line_num = loc.pos.line.to_string();
+                let margin = " ".repeat(line_num.len());
+                writeln!(f, "{}:{}:{}: {}", loc.file, loc.pos.line, loc.pos.col, e)?;
+                writeln!(f, "{} |", margin)?;
+                writeln!(f, "{} | {}", line_num, loc.line)?;
+                write!(f, "{} | {}^", margin, " ".repeat(loc.pos.col - 1))
+            }
         }
     }
 }
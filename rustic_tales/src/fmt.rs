@@ -0,0 +1,69 @@
+use std::fs;
+
+use script::lint::near_misses;
+use script::print::print;
+use script::token::{tokenize, Token};
+
+use crate::err::Result;
+
+// Rewrites every story in paths the canonical way (or with check, just says which ones aren't).
+// Returns false if something went wrong or, with check, if any story needs formatting.
+pub fn fmt_stories(paths: &[String], check: bool) -> bool {
+    let mut ok = true;
+    for path in paths {
+        match fmt_story(path, check) {
+            Ok(formatted) => ok &= formatted,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                ok = false;
+            }
+        }
+    }
+    ok
+}
+
+// Returns whether the story is formatted now
+fn fmt_story(path: &str, check: bool) -> Result<bool> {
+    let source = fs::read_to_string(path)?;
+    // The formatter can't fix these, since there's no telling what they were meant to be
+    for (pos, msg) in near_misses(&source) {
+        eprintln!("{}:{}:{}: warning: {}", path, pos.line, pos.col, msg);
+    }
+    let formatted = format_source(&source);
+    if formatted == source {
+        Ok(true)
+    } else if check {
+        println!("{} needs formatting", path);
+        Ok(false)
+    } else {
+        fs::write(path, formatted)?;
+        println!("Formatted {}", path);
+        Ok(true)
+    }
+}
+
+pub fn format_source(source: &str) -> String {
+    print(&sections_on_own_lines(tokenize(source)))
+}
+
+// Sections can start partway through a line, but that's easy to miss when reading a story
+fn sections_on_own_lines(tkns: Vec<Token>) -> Vec<Token> {
+    let mut ret: Vec<Token> = Vec::with_capacity(tkns.len());
+    for tkn in tkns {
+        if tkn.is_sect_start() {
+            match ret.last_mut() {
+                Some(Token::Text(s)) if !s.ends_with('\n') => s.push('\n'),
+                Some(
+                    Token::Variable(_)
+                    | Token::VariableOr(..)
+                    | Token::Symbol(_)
+                    | Token::Char(_)
+                    | Token::PageEnd,
+                ) => ret.push(Token::Text("\n".to_owned())),
+                _ => {}
+            }
+        }
+        ret.push(tkn);
+    }
+    ret
+}
@@ -0,0 +1,373 @@
+use std::collections::HashSet;
+
+use chrono::NaiveTime;
+
+use crate::commands::registry::StoryCommand;
+use crate::commands::story_command::{Arg, Comparison};
+use crate::err::Result;
+use crate::storyteller::builtins::*;
+use crate::storyteller::story::{Section, Story};
+use crate::storyteller::Unit;
+
+// Where a jump can go
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Target {
+    Section(usize),
+    Dynamic(String),      // depends on variables, so it could be any section
+    File(String, String), // (file, section)
+    End,                  // end_story
+}
+
+#[derive(Debug, Clone)]
+pub struct Edge {
+    pub from: usize,
+    pub to: Target,
+    pub label: String, // when the jump happens
+}
+
+// How a story's sections connect to each other, worked out without telling it. Sections are
+// numbered the same way as in Story::get_sections.
+#[derive(Debug, Clone)]
+pub struct StoryGraph {
+    name: String,
+    sections: Vec<String>,
+    edges: Vec<Edge>,
+    returns: Vec<(usize, usize)>, // (section with a return, section whose goto it goes back to)
+}
+
+fn show(arg: &Arg<String>) -> String {
+    match arg {
+        Arg::Fixed(s) => s.clone(),
+        Arg::Dynamic { arg, .. } => arg.clone(),
+    }
+}
+
+fn show_time(arg: &Arg<NaiveTime>) -> String {
+    match arg {
+        Arg::Fixed(t) => t.format("%H:%M").to_string(),
+        Arg::Dynamic { arg, .. } => arg.clone(),
+    }
+}
+
+fn comparison(cmp: Comparison, negated: bool) -> &'static str {
+    match (cmp, negated) {
+        (Comparison::Eq, false) | (Comparison::Ne, true) => "=",
+        (Comparison::Ne, false) | (Comparison::Eq, true) => "!=",
+        (Comparison::Lt, false) => "<",
+        (Comparison::Lt, true) => ">=",
+        (Comparison::Gt, false) => ">",
+        (Comparison::Gt, true) => "<=",
+    }
+}
+
+fn commands<'a>(story: &'a Story, sect: &'a Section) -> impl Iterator<Item = &'a dyn StoryCommand> {
+    sect.unit_idxes()
+        .filter_map(move |idx| match &story.get_contents()[idx] {
+            Unit::Command(call, _) => Some(&*call.cmd),
+            _ => None,
+        })
+}
+
+// Whether nothing after cmd in its section can happen. A goto only counts if it can't come back.
+fn always_leaves(cmd: &dyn StoryCommand, goto_returns: impl Fn(&Arg<String>) -> bool) -> bool {
+    if let Some(JumpIf { otherwise, .. }) = cmd.downcast_ref() {
+        otherwise.is_some()
+    } else if let Some(JumpIfTimeBetween { otherwise, .. }) = cmd.downcast_ref() {
+        otherwise.is_some()
+    } else if let Some(Goto(sect)) = cmd.downcast_ref() {
+        !goto_returns(sect)
+    } else {
+        cmd.is::<JumpRandom>()
+            || cmd.is::<JumpToFile>()
+            || cmd.is::<Return>()
+            || cmd.is::<EndStory>()
+    }
+}
+
+impl StoryGraph {
+    pub fn new(story: &Story) -> StoryGraph {
+        let sections = story.get_sections();
+        let target = |arg: &Arg<String>| match arg {
+            // loading the story already made sure fixed sections exist
+            Arg::Fixed(sect) => Target::Section(story.find_section(sect).unwrap_or_default()),
+            Arg::Dynamic { arg, .. } => Target::Dynamic(arg.clone()),
+        };
+
+        let has_return: Vec<_> = sections
+            .iter()
+            .map(|sect| commands(story, sect).any(|cmd| cmd.is::<Return>()))
+            .collect();
+        // (there's no telling with a section that depends on variables)
+        let goto_returns = |sect: &Arg<String>| match target(sect) {
+            Target::Section(sect) => has_return[sect],
+            _ => true,
+        };
+
+        let mut edges = Vec::new();
+        for (from, sect) in sections.iter().enumerate() {
+            let mut goes_on = true;
+            for cmd in commands(story, sect) {
+                let mut add = |to: Target, label: String| edges.push(Edge { from, to, label });
+                if let Some(JumpIf {
+                    cmp,
+                    lhs,
+                    rhs,
+                    then,
+                    otherwise,
+                }) = cmd.downcast_ref()
+                {
+                    let (lhs, rhs) = (show(lhs), show(rhs));
+                    let cond = format!("{} {} {}", lhs, comparison(*cmp, false), rhs);
+                    add(target(then), cond);
+                    if let Some(otherwise) = otherwise {
+                        let cond = format!("{} {} {}", lhs, comparison(*cmp, true), rhs);
+                        add(target(otherwise), cond);
+                    }
+                } else if let Some(JumpIfTimeBetween {
+                    start,
+                    end,
+                    then,
+                    otherwise,
+                }) = cmd.downcast_ref()
+                {
+                    let range = format!("{} to {}", show_time(start), show_time(end));
+                    add(target(then), range.clone());
+                    if let Some(otherwise) = otherwise {
+                        add(target(otherwise), format!("not {}", range));
+                    }
+                } else if let Some(JumpRandom { choices, .. }) = cmd.downcast_ref() {
+                    let total: u32 = choices.iter().map(|&(_, w)| w).sum();
+                    for (sect, weight) in choices {
+                        add(target(sect), format!("random ({}/{})", weight, total));
+                    }
+                } else if let Some(Goto(sect)) = cmd.downcast_ref() {
+                    add(target(sect), "goto".to_owned());
+                } else if let Some(JumpToFile { file, section }) = cmd.downcast_ref() {
+                    let to = Target::File(show(file), show(section));
+                    add(to, "jump_to_file".to_owned());
+                } else if cmd.is::<EndStory>() {
+                    add(Target::End, "end_story".to_owned());
+                }
+                if always_leaves(cmd, goto_returns) {
+                    goes_on = false;
+                    break;
+                }
+            }
+            if goes_on && sect.falls_through() && from + 1 < sections.len() {
+                edges.push(Edge {
+                    from,
+                    to: Target::Section(from + 1),
+                    label: "fallthrough".to_owned(),
+                });
+            }
+        }
+
+        // A return goes back to whichever section did the goto
+        let returns = edges
+            .iter()
+            .filter(|edge| edge.label == "goto")
+            .filter_map(|edge| match edge.to {
+                Target::Section(to) if has_return[to] => Some((to, edge.from)),
+                _ => None,
+            })
+            .collect();
+        StoryGraph {
+            name: story.file_name(0).clone(),
+            sections: sections.iter().map(|s| s.get_name().clone()).collect(),
+            edges,
+            returns,
+        }
+    }
+    pub fn from_file(path: &str, tab_width: usize) -> Result<StoryGraph> {
+        Ok(StoryGraph::new(&Story::from_file(path, tab_width)?))
+    }
+
+    // Whether each section can be reached from the first one. Jumps that depend on variables
+    // aren't followed, since there's no telling where they go.
+    fn reachable(&self) -> Vec<bool> {
+        let mut seen = vec![false; self.sections.len()];
+        let mut todo = vec![0];
+        while let Some(sect) = todo.pop() {
+            if sect >= seen.len() || seen[sect] {
+                continue;
+            }
+            seen[sect] = true;
+            for edge in self.edges.iter().filter(|edge| edge.from == sect) {
+                if let Target::Section(to) = edge.to {
+                    todo.push(to);
+                }
+            }
+        }
+        seen
+    }
+    pub fn unreachable(&self) -> Vec<usize> {
+        let reachable = self.reachable();
+        (0..self.sections.len())
+            .filter(|&sect| !reachable[sect])
+            .collect()
+    }
+    // Sections the story just stops at the end of, without saying to (with end_story). A story
+    // with only one section obviously stops at the end of it, so that doesn't count.
+    pub fn dead_ends(&self) -> Vec<usize> {
+        if self.sections.len() < 2 {
+            return Vec::new();
+        }
+        let leaves: HashSet<_> = self
+            .edges
+            .iter()
+            .map(|edge| edge.from)
+            .chain(self.returns.iter().map(|&(from, _)| from))
+            .collect();
+        (0..self.sections.len())
+            .filter(|sect| !leaves.contains(sect))
+            .collect()
+    }
+    pub fn has_dynamic_jumps(&self) -> bool {
+        self.edges
+            .iter()
+            .any(|edge| matches!(edge.to, Target::Dynamic(_)))
+    }
+
+    // What's worth knowing about the graph, for people
+    pub fn summary(&self) -> String {
+        let mut lines = vec![format!("{}:", self.name)];
+        let list = |sects: Vec<usize>| {
+            let names: Vec<_> = sects
+                .iter()
+                .map(|&sect| format!("'{}'", self.sections[sect]))
+                .collect();
+            names.join(", ")
+        };
+        let unreachable = self.unreachable();
+        if !unreachable.is_empty() {
+            let first = &self.sections[0];
+            let msg = format!("  can't be reached from '{}': {}", first, list(unreachable));
+            lines.push(msg);
+        }
+        let dead_ends = self.dead_ends();
+        if !dead_ends.is_empty() {
+            lines.push(format!("  lead nowhere: {}", list(dead_ends)));
+        }
+        if lines.len() == 1 {
+            lines.push("  every section can be reached and leads somewhere".to_owned());
+        }
+        if self.has_dynamic_jumps() {
+            lines.push("  (some jumps depend on variables, so they could go anywhere)".to_owned());
+        }
+        lines.join("\n")
+    }
+
+    // The graph in Graphviz's DOT language (e.g. for `dot -Tsvg`). Sections that can't be reached
+    // are dashed and ones that lead nowhere are red.
+    pub fn to_dot(&self) -> String {
+        fn quote(s: &str) -> String {
+            format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+        }
+        let reachable = self.reachable();
+        let dead_ends = self.dead_ends();
+
+        let mut dot = format!("digraph {} {{\n    node [shape=box];\n", quote(&self.name));
+        for (sect, name) in self.sections.iter().enumerate() {
+            let mut attrs = vec![format!("label={}", quote(name))];
+            if !reachable[sect] {
+                attrs.push("style=dashed".to_owned());
+            }
+            if dead_ends.contains(&sect) {
+                attrs.push("color=red".to_owned());
+            }
+            dot += &format!("    s{} [{}];\n", sect, attrs.join(", "));
+        }
+
+        // Nodes for places that aren't sections of this story
+        let mut others: Vec<&Target> = Vec::new();
+        for edge in &self.edges {
+            if !matches!(edge.to, Target::Section(_)) && !others.contains(&&edge.to) {
+                others.push(&edge.to);
+            }
+        }
+        for (i, other) in others.iter().enumerate() {
+            let (label, shape) = match other {
+                Target::Dynamic(sect) => (sect.clone(), "diamond"),
+                Target::File(file, sect) => (format!("{}: {}", file, sect), "note"),
+                Target::End => ("the end".to_owned(), "doublecircle"),
+                Target::Section(_) => unreachable!("sections are not other places"),
+            };
+            dot += &format!("    o{} [label={}, shape={}];\n", i, quote(&label), shape);
+        }
+
+        for edge in &self.edges {
+            let to = match &edge.to {
+                Target::Section(sect) => format!("s{}", sect),
+                other => {
+                    let i = others.iter().position(|&o| o == other).unwrap_or_default();
+                    format!("o{}", i)
+                }
+            };
+            let label = quote(&edge.label);
+            dot += &format!("    s{} -> {} [label={}];\n", edge.from, to, label);
+        }
+        for (from, to) in &self.returns {
+            dot += &format!(
+                "    s{} -> s{} [label=\"return\", style=dashed];\n",
+                from, to
+            );
+        }
+        dot += "}\n";
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(story: &str) -> StoryGraph {
+        StoryGraph::new(&story.parse().expect("test stories should load"))
+    }
+
+    #[test]
+    fn unreachable_and_dead_ends() {
+        let g = graph(concat!(
+            "Start\n",
+            "{{ jump_if_eq : ${{X}} |,| 1 |,| a |,| b }}\n",
+            "#=$ a $=#\n",
+            "A\n",
+            "{{ end_story :}}\n",
+            "#=$ b $=#\n",
+            "B\n",
+            "#=$ lost $=#\n",
+            "Nobody comes here\n",
+            "{{ goto : a }}\n",
+        ));
+        assert_eq!(g.unreachable(), vec![3]);
+        assert_eq!(g.dead_ends(), vec![2]);
+        assert!(!g.has_dynamic_jumps());
+        let dot = g.to_dot();
+        assert!(dot.contains("s0 -> s1 [label=\"${{X}} = 1\"];"));
+        assert!(dot.contains("s0 -> s2 [label=\"${{X}} != 1\"];"));
+    }
+
+    #[test]
+    fn fallthrough_and_return() {
+        let g = graph(concat!(
+            "{{{\n",
+            "fallthrough : yes\n",
+            "}}}\n",
+            "Start\n",
+            "{{ goto : shared }}\n",
+            "{{ goto : done }}\n",
+            "#=$ skipped $=#\n",
+            "Nothing gets here, since the goto above never comes back\n",
+            "#=$ done $=#\n",
+            "{{ end_story :}}\n",
+            "#=$ shared $=#\n",
+            "Shared\n",
+            "{{ return :}}\n",
+        ));
+        assert_eq!(g.unreachable(), vec![1]);
+        assert!(g.dead_ends().is_empty());
+        let dot = g.to_dot();
+        assert!(!dot.contains("s0 -> s1"));
+        assert!(dot.contains("s3 -> s0 [label=\"return\", style=dashed];"));
+    }
+}
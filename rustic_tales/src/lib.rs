@@ -0,0 +1,29 @@
+extern crate either;
+extern crate globset;
+extern crate humantime;
+extern crate image;
+extern crate regex;
+extern crate ron;
+extern crate script;
+extern crate serde;
+extern crate terminal_size;
+extern crate termios;
+
+pub mod ansi;
+pub mod buffer;
+pub mod check;
+pub mod clock;
+pub mod commands;
+pub mod debug;
+pub mod err;
+pub mod fmt;
+pub mod graph;
+pub mod options;
+pub mod save;
+pub mod storyteller;
+pub mod utils;
+
+// What's needed to add commands of your own (see commands/registry.rs)
+pub use commands::registry::{register, Arity, StoryCommand};
+pub use commands::story_command::{parse_arg, Arg};
+pub use storyteller::{StoryTeller, Transition};
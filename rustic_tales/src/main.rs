@@ -1,36 +1,74 @@
-extern crate either;
-extern crate globset;
-extern crate humantime;
-extern crate image;
-extern crate regex;
-extern crate ron;
-extern crate script;
-extern crate serde;
-extern crate terminal_size;
-extern crate termios;
-
-mod ansi;
-mod buffer;
-mod commands;
-mod debug;
-mod err;
-mod options;
-mod storyteller;
-mod utils;
-
-use ansi::TermAction;
-use buffer::TermBuffer;
-use debug::debug_menu;
-use err::Result;
-use options::Options;
-use storyteller::{StatefulStoryTeller, StoryTeller, Telling};
-use utils::*;
-
-fn tell_story<'a>(mut st: StoryTeller<'a, Telling>, opts: &'a Options) {
+use rustic_tales::ansi::TermAction;
+use rustic_tales::buffer::TermBuffer;
+use rustic_tales::check::check_stories;
+use rustic_tales::debug::debug_menu;
+use rustic_tales::err::{RTError, Result};
+use rustic_tales::fmt::fmt_stories;
+use rustic_tales::graph::StoryGraph;
+use rustic_tales::options::{BufOptions, Options, STOptions};
+use rustic_tales::save::{autosave, choose_save, clear_autosave, load_autosave, slots, SaveFile};
+use rustic_tales::storyteller::meta::StoryMeta;
+use rustic_tales::storyteller::{StatefulStoryTeller, StoryTeller, Telling};
+use rustic_tales::utils::*;
+
+// Shows whatever the story says about itself before it starts
+fn introduce_story(meta: &StoryMeta) {
+    if !meta.has_intro() {
+        return;
+    }
+    clear_screen();
+    if let Some(title) = &meta.title {
+        println!("{}", title);
+    }
+    if let Some(author) = &meta.author {
+        println!("by {}", author);
+    }
+    if let Some(blurb) = &meta.blurb {
+        println!("\n{}", blurb);
+    }
+    if !meta.content_warnings.is_empty() {
+        println!("\nContent warnings: {}", meta.content_warnings.join(", "));
+    }
+    if let Some((cols, rows)) = meta.min_term_size {
+        let (w, h) = terminal_dims();
+        if w < cols || h < rows {
+            println!(
+                "\nThis story is meant for a terminal that's at least {}x{}, but yours is {}x{}.",
+                cols, rows, w, h
+            );
+        }
+    }
+    wait_for_enter("\nPress enter to begin...");
+}
+
+// Shows the page that just filled up until the reader moves on, letting them look further back
+// (with b and f) in the meantime
+fn wait_for_page_turn(buf: &mut TermBuffer) {
+    buf.look_back();
+    exhaust_kb();
+    loop {
+        buf.clear_and_dump();
+        match wait_for_kb_with_prompt("\nNext page...") {
+            b'b' => {
+                buf.look_back();
+            }
+            b'f' => {
+                buf.look_forward();
+                if !buf.is_looking_back() {
+                    break;
+                }
+            }
+            _ => break,
+        }
+    }
+    buf.stop_looking_back();
+}
+
+fn tell_story<'a>(mut st: StoryTeller<'a, Telling>, st_opts: &'a STOptions, buf_opts: &BufOptions) {
     let orig_term_settings = no_term_echo();
 
-    st.setup(opts.get_story_opts(), orig_term_settings);
-    let mut buf = TermBuffer::new(opts.get_buf_opts());
+    st.setup(st_opts, orig_term_settings);
+    let mut buf = TermBuffer::new(buf_opts);
     let mut narrator = StatefulStoryTeller::from_telling(st);
     loop {
         let info = narrator.step(&mut buf);
@@ -40,9 +78,7 @@ fn tell_story<'a>(mut st: StoryTeller<'a, Telling>, opts: &'a Options) {
         buf.set_info(narrator.state_str(), narrator.info_str());
 
         if buf.just_turned_page() {
-            buf.clear_and_dump_prev_page();
-            exhaust_kb();
-            wait_for_kb_with_prompt("\nNext page...");
+            wait_for_page_turn(&mut buf);
         } else if buf.just_modified() {
             buf.clear_and_dump();
         }
@@ -54,13 +90,118 @@ fn tell_story<'a>(mut st: StoryTeller<'a, Telling>, opts: &'a Options) {
         }
     }
 
+    // Quitting keeps the reader's place, and finishing means there's nothing left to continue
+    let save = narrator.save();
+    let saved = if narrator.quit_early() {
+        autosave(&st_opts.saves_directory, &save)
+    } else {
+        clear_autosave(&st_opts.saves_directory, &save.story)
+    };
+    if let Err(e) = saved {
+        eprintln!("\nCould not update the autosave because '{}'", e);
+    }
+
     TermAction::ResetColor.execute_raw();
     wait_for_kb_with_prompt("\nThe end...");
     change_term(orig_term_settings);
 }
 
+// Returns whether the story got told
+fn resume_story(save: &SaveFile, opts: &Options) -> bool {
+    match StoryTeller::<Telling>::from_save(save, opts.get_buf_opts().tab_width) {
+        Ok(st) => {
+            let st_opts = st.get_meta().apply_to(opts.get_story_opts());
+            tell_story(st, &st_opts, opts.get_buf_opts());
+            true
+        }
+        Err(e) => {
+            println!("Could not pick the story back up because '{}'", e);
+            false
+        }
+    }
+}
+
+// Validates stories without telling them (all of them if none are given)
+fn check(paths: &[String], opts: &Options) -> Result<()> {
+    let paths = if paths.is_empty() {
+        all_stories(opts.get_ignored(), opts.get_story_folder())?
+    } else {
+        paths.to_vec()
+    };
+    if !check_stories(&paths, opts) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// Rewrites stories the canonical way (all of them if none are given). With --check, nothing
+// gets rewritten, but it fails if anything would have been.
+fn fmt(args: &[String], opts: &Options) -> Result<()> {
+    let check = args.iter().any(|arg| arg == "--check");
+    let paths: Vec<_> = args
+        .iter()
+        .filter(|&arg| arg != "--check")
+        .cloned()
+        .collect();
+    let paths = if paths.is_empty() {
+        all_stories(opts.get_ignored(), opts.get_story_folder())?
+    } else {
+        paths
+    };
+    if !fmt_stories(&paths, check) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// Prints the section graph of each story (all of them if none are given) as DOT, and says
+// which sections can't be reached or lead nowhere. The summary goes to stderr so the DOT can be
+// piped straight into Graphviz.
+fn graph(paths: &[String], opts: &Options) -> Result<()> {
+    let paths = if paths.is_empty() {
+        all_stories(opts.get_ignored(), opts.get_story_folder())?
+    } else {
+        paths.to_vec()
+    };
+    for path in &paths {
+        match StoryGraph::from_file(path, opts.get_buf_opts().tab_width) {
+            Ok(graph) => {
+                print!("{}", graph.to_dot());
+                eprintln!("{}", graph.summary());
+            }
+            Err(e) => eprintln!("Could not load {} because '{}'", path, e),
+        }
+    }
+    Ok(())
+}
+
+// Pulls "--seed N" out of args, so the same random choices get made every time
+fn take_seed(args: &mut Vec<String>) -> Result<Option<u64>> {
+    let idx = match args.iter().position(|arg| arg == "--seed") {
+        Some(idx) => idx,
+        None => return Ok(None),
+    };
+    args.remove(idx);
+    if idx < args.len() {
+        Ok(Some(args.remove(idx).parse()?))
+    } else {
+        Err(RTError::InvalidInput(
+            "--seed needs a number after it".to_owned(),
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MenuChoice {
+    NewStory,
+    Continue,
+    LoadSave,
+    Debug,
+    Goodbye,
+}
+
 fn main() -> Result<()> {
-    let options = match Options::from_file("options.ron") {
+    let mut options = match Options::from_file("options.ron") {
         Ok(opts) => opts,
         Err(_) => {
             let temp = Options::default();
@@ -69,31 +210,77 @@ fn main() -> Result<()> {
             temp
         }
     };
+
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(seed) = take_seed(&mut args)? {
+        options.set_seed(seed);
+    }
+    match args.first().map(String::as_str) {
+        Some("check") => return check(&args[1..], &options),
+        Some("fmt") => return fmt(&args[1..], &options),
+        Some("graph") => return graph(&args[1..], &options),
+        Some(cmd) => {
+            eprintln!(
+                "Unrecognized subcommand '{}'. Try 'check', 'fmt' or 'graph'.",
+                cmd
+            );
+            std::process::exit(2);
+        }
+        None => {}
+    }
+
+    let tab_width = options.get_buf_opts().tab_width;
     let mut skip_enter = true;
     loop {
         if !skip_enter {
             wait_for_enter("Press enter to continue...");
         }
         skip_enter = false;
-        match menu(&["Tell me a story", "Debug Stuff", "Goodbye"], None, true) {
+        let continuing = load_autosave(options.get_saves_folder());
+        let mut choices = vec![(MenuChoice::NewStory, "Tell me a story".to_owned())];
+        if let Some(save) = &continuing {
+            let label = format!("Continue {}", save.story_name());
+            choices.push((MenuChoice::Continue, label));
+        }
+        if !slots(options.get_saves_folder()).is_empty() {
+            choices.push((MenuChoice::LoadSave, "Load a save".to_owned()));
+        }
+        choices.push((MenuChoice::Debug, "Debug Stuff".to_owned()));
+        choices.push((MenuChoice::Goodbye, "Goodbye".to_owned()));
+
+        let labels: Vec<_> = choices.iter().map(|(_, label)| label).collect();
+        match menu(&labels, None, true).map(|idx| choices[idx].0) {
             Err(e) => println!(
                 "I did not understand your choice.\n{}\nPlease try again.\n",
                 e
             ),
-            Ok(0) => match choose_story(options.get_ignored(), options.get_story_folder()) {
-                Ok(story) => match StoryTeller::<Telling>::new(&story) {
-                    Ok(st) => {
-                        skip_enter = true;
-                        tell_story(st, &options);
-                    }
-                    Err(e) => println!("Could not parse story because '{}'", e),
-                },
-                Err(e) => println!("I could not understand your choice\n{}", e),
+            Ok(MenuChoice::NewStory) => {
+                match choose_story(options.get_ignored(), options.get_story_folder()) {
+                    Ok(story) => match StoryTeller::<Telling>::new(&story, tab_width) {
+                        Ok(st) => {
+                            skip_enter = true;
+                            let st_opts = st.get_meta().apply_to(options.get_story_opts());
+                            introduce_story(st.get_meta());
+                            tell_story(st, &st_opts, options.get_buf_opts());
+                        }
+                        Err(e) => println!("Could not parse story because '{}'", e),
+                    },
+                    Err(e) => println!("I could not understand your choice\n{}", e),
+                }
+            }
+            Ok(MenuChoice::Continue) => {
+                if let Some(save) = &continuing {
+                    skip_enter = resume_story(save, &options);
+                }
+            }
+            Ok(MenuChoice::LoadSave) => match choose_save(options.get_saves_folder()) {
+                Ok(save) => skip_enter = resume_story(&save, &options),
+                Err(e) => println!("I could not load that save\n{}", e),
             },
-            Ok(1) => {
+            Ok(MenuChoice::Debug) => {
                 skip_enter = debug_menu(&options)?;
             }
-            Ok(_) => break,
+            Ok(MenuChoice::Goodbye) => break,
         }
     }
     clear_screen();
@@ -7,12 +7,15 @@ use ron::de::from_reader;
 use ron::ser::{to_writer_pretty, PrettyConfig};
 use serde::{Deserialize, Serialize};
 
+use crate::clock::Clock;
 use crate::err::{RTError, Result};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DisplayUnit {
     Char,
     Word,
+    // Only backspace uses this; the story is still shown word by word (see ScrollRate::Lines)
+    Line,
 }
 
 impl FromStr for DisplayUnit {
@@ -60,6 +63,26 @@ pub struct STOptions {
     pub disp_by: DisplayUnit,
     pub stories_directory: String,
     pub prompt_when_wait: Option<char>,
+    // Lets a story's header change things like the scroll rate
+    #[serde(default = "yes")]
+    pub allow_story_overrides: bool,
+    // Makes jump_random go the same way every time (for when a branch needs debugging)
+    #[serde(default)]
+    pub seed: Option<u64>,
+    // e.g. Fixed("2021-10-31T02:00:00") to see what a story does at that time
+    #[serde(default)]
+    pub clock: Clock,
+    // Where stories in progress get saved
+    #[serde(default = "default_saves_directory")]
+    pub saves_directory: String,
+}
+
+fn yes() -> bool {
+    true
+}
+
+fn default_saves_directory() -> String {
+    "saves".to_owned()
 }
 
 impl Default for STOptions {
@@ -70,6 +93,10 @@ impl Default for STOptions {
             disp_by: DisplayUnit::Word,
             stories_directory: "rustic_tales/stories".to_owned(),
             prompt_when_wait: Some('>'),
+            allow_story_overrides: true,
+            seed: None,
+            clock: Clock::System,
+            saves_directory: default_saves_directory(),
         }
     }
 }
@@ -80,6 +107,13 @@ pub struct BufOptions {
     pub max_num_rows: Option<usize>,
     pub max_num_cols: Option<usize>,
     pub display_storyteller_state: bool,
+    // How many columns apart tab stops are
+    #[serde(default = "default_tab_width")]
+    pub tab_width: usize,
+}
+
+fn default_tab_width() -> usize {
+    4
 }
 
 impl Default for BufOptions {
@@ -89,6 +123,7 @@ impl Default for BufOptions {
             max_num_cols: Some(100),
             max_num_rows: Some(40),
             display_storyteller_state: false,
+            tab_width: default_tab_width(),
         }
     }
 }
@@ -129,7 +164,13 @@ impl Options {
     pub fn get_story_folder(&self) -> &String {
         &self.st_opts.stories_directory
     }
+    pub fn get_saves_folder(&self) -> &String {
+        &self.st_opts.saves_directory
+    }
     pub fn get_buf_opts(&self) -> &BufOptions {
         &self.buf_opts
     }
+    pub fn set_seed(&mut self, seed: u64) {
+        self.st_opts.seed = Some(seed);
+    }
 }
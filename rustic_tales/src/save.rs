@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::err::{RTError, Result};
+use crate::storyteller::story::SavedPlace;
+use crate::utils::menu;
+
+// Quitting saves here, and "Continue" picks back up from here
+const AUTOSAVE: &str = "autosave";
+
+// A story in progress
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveFile {
+    pub story: String, // path to the story file
+    pub hash: u64,     // Story::hash of the story when it was saved
+    pub place: SavedPlace,
+    pub env: HashMap<String, String>,
+    pub saved_at: NaiveDateTime,
+}
+
+impl SaveFile {
+    pub fn to_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        serde_json::to_writer_pretty(File::create(path)?, self)?;
+        Ok(())
+    }
+    pub fn from_file(path: impl AsRef<Path>) -> Result<SaveFile> {
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+    pub fn story_name(&self) -> String {
+        Path::new(&self.story)
+            .file_name()
+            .map_or_else(|| self.story.clone(), |name| name.to_string_lossy().into_owned())
+    }
+    // What it looks like in the list of saves
+    pub fn label(&self, slot: &str) -> String {
+        format!(
+            "{} ({}, saved {})",
+            slot,
+            self.story_name(),
+            self.saved_at.format("%Y-%m-%d %H:%M")
+        )
+    }
+}
+
+fn slot_path(dir: &str, slot: &str) -> PathBuf {
+    Path::new(dir).join(format!("{}.json", slot))
+}
+
+pub fn save_to_slot(dir: &str, slot: &str, save: &SaveFile) -> Result<()> {
+    // Slot names end up as file names, so keep them tame
+    let tame = !slot.trim().is_empty()
+        && slot
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == ' ' || c == '-' || c == '_');
+    if !tame || slot == AUTOSAVE {
+        let msg = format!(
+            "'{}' can't be used as a save name. Stick to letters, numbers, spaces, '-' and '_' (and not '{}')",
+            slot, AUTOSAVE
+        );
+        return Err(RTError::InvalidInput(msg));
+    }
+    save.to_file(slot_path(dir, slot))
+}
+
+pub fn autosave(dir: &str, save: &SaveFile) -> Result<()> {
+    save.to_file(slot_path(dir, AUTOSAVE))
+}
+
+// Nothing to continue if there's no autosave (or it's unreadable)
+pub fn load_autosave(dir: &str) -> Option<SaveFile> {
+    SaveFile::from_file(slot_path(dir, AUTOSAVE)).ok()
+}
+
+// Once a story's over, there's nothing to continue
+pub fn clear_autosave(dir: &str, story: &str) -> Result<()> {
+    if load_autosave(dir).is_some_and(|save| save.story == story) {
+        fs::remove_file(slot_path(dir, AUTOSAVE))?;
+    }
+    Ok(())
+}
+
+// The names of all the save slots (not including the autosave)
+pub fn slots(dir: &str) -> Vec<String> {
+    let mut slots: Vec<_> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter_map(|name| name.strip_suffix(".json").map(str::to_owned))
+        .filter(|name| name != AUTOSAVE)
+        .collect();
+    slots.sort();
+    slots
+}
+
+pub fn choose_save(dir: &str) -> Result<SaveFile> {
+    let slots = slots(dir);
+    // Broken saves still get listed, so picking them says what's wrong
+    let labels: Vec<_> = slots
+        .iter()
+        .map(|slot| {
+            SaveFile::from_file(slot_path(dir, slot))
+                .map(|save| save.label(slot))
+                .unwrap_or_else(|_| slot.clone())
+        })
+        .collect();
+    let idx = menu(&labels, None, true)?;
+    SaveFile::from_file(slot_path(dir, &slots[idx]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::clock::Clock;
+    use crate::storyteller::story::Story;
+    use crate::utils::scratch_dir;
+
+    fn save_of(story: &str) -> SaveFile {
+        let parsed: Story = "Once upon a time\n".parse().unwrap();
+        SaveFile {
+            story: story.to_owned(),
+            hash: parsed.hash(),
+            place: parsed.saved_place(),
+            env: HashMap::from([("NAME".to_owned(), "Ada".to_owned())]),
+            saved_at: Clock::System.now(),
+        }
+    }
+
+    #[test]
+    fn slot_names() {
+        let dir = scratch_dir("save_slots");
+        let dir = dir.to_str().unwrap();
+        let save = save_of("story.txt");
+        for bad in ["../escape", "a/b", "dot.json", "", "  ", AUTOSAVE] {
+            assert!(
+                matches!(save_to_slot(dir, bad, &save), Err(RTError::InvalidInput(_))),
+                "'{}' should not be a save name",
+                bad
+            );
+        }
+        assert!(slots(dir).is_empty());
+
+        save_to_slot(dir, "chapter 2_b-1", &save).unwrap();
+        autosave(dir, &save).unwrap();
+        // the autosave isn't a slot
+        assert_eq!(slots(dir), ["chapter 2_b-1"]);
+        let loaded = SaveFile::from_file(slot_path(dir, "chapter 2_b-1")).unwrap();
+        assert_eq!(loaded.story, save.story);
+        assert_eq!(loaded.hash, save.hash);
+        assert_eq!(loaded.place, save.place);
+        assert_eq!(loaded.env, save.env);
+    }
+
+    #[test]
+    fn autosaves() {
+        let dir = scratch_dir("save_autosave");
+        let dir = dir.to_str().unwrap();
+        assert!(load_autosave(dir).is_none());
+        autosave(dir, &save_of("story.txt")).unwrap();
+        assert_eq!(load_autosave(dir).unwrap().story, "story.txt");
+
+        // Finishing some other story leaves it alone
+        clear_autosave(dir, "other.txt").unwrap();
+        assert!(load_autosave(dir).is_some());
+        clear_autosave(dir, "story.txt").unwrap();
+        assert!(load_autosave(dir).is_none());
+    }
+}
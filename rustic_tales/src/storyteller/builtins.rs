@@ -0,0 +1,627 @@
+use std::time::Duration;
+
+use chrono::NaiveTime;
+use rand::distributions::{Distribution, WeightedIndex};
+
+use crate::buffer::TermBuffer;
+use crate::clock::time_between;
+use crate::commands::registry::{Arity, Registry, StoryCommand};
+use crate::commands::story_command::*;
+use crate::commands::vars::{add_to, compare_numbers};
+use crate::commands::{self, img_to_ascii, img_to_term};
+use crate::err::{Expected, RTError, Result};
+use crate::options::DisplayUnit;
+use crate::utils;
+
+use super::storyteller_base::StoryTeller;
+use super::storyteller_states::*;
+
+// The commands that come with the program, and how many arguments each takes
+pub fn register_builtins(registry: &mut Registry) {
+    registry.register::<Backspace>("backspace", Arity::between(2, 4));
+    registry.register::<DisplayImg>("display_img", Arity::between(1, 2));
+    registry.register::<PromptYesNo>("prompt_yesno", Arity::between(1, 2));
+    for func in ["jump_if_eq", "jump_if_ne", "jump_if_lt", "jump_if_gt"] {
+        registry.register::<JumpIf>(func, Arity::between(3, 4));
+    }
+    registry.register::<JumpIfTimeBetween>("jump_if_time_between", Arity::between(3, 4));
+    registry.register::<JumpToFile>("jump_to_file", Arity::between(1, 2));
+    registry.register::<Goto>("goto", Arity::exactly(1));
+    registry.register::<Return>("return", Arity::at_least(0));
+    registry.register::<EndStory>("end_story", Arity::at_least(0));
+    registry.register::<Fallthrough>("fallthrough", Arity::exactly(1));
+    registry.register::<JumpRandom>("jump_random", Arity::at_least(1));
+    registry.register::<Pause>("pause", Arity::exactly(1));
+    registry.register::<ForceInput>("force_input", Arity::exactly(1));
+    registry.register::<ChoiceMenu>("choice_menu", Arity::at_least(2));
+    registry.register::<WaitKb>("wait_kb", Arity::at_least(0));
+    registry.register::<MoveCursorBack>("move_cursor_back", Arity::exactly(1));
+    registry.register::<ClearScreen>("clear_screen", Arity::at_least(0));
+    registry.register::<Repeat>("repeat", Arity::exactly(3));
+    registry.register::<RandomWordGenerator>("random_word_generator", Arity::exactly(1));
+    registry.register::<Set>("set", Arity::exactly(2));
+    // incr and decr are just adding 1 and -1
+    for func in ["incr", "decr", "add"] {
+        let arity = if func == "add" { 2 } else { 1 };
+        registry.register::<Add>(func, Arity::exactly(arity));
+    }
+}
+
+// Arguments that are just a word to look out for, like term in {{ display_img : cat.png |,| term }}
+fn flag(args: &[String], i: usize, val: &str) -> bool {
+    args.get(i).is_some_and(|a| a.eq_ignore_ascii_case(val))
+}
+
+fn opt(arg: &Option<Arg<String>>) -> Option<&str> {
+    arg.as_ref().and_then(Arg::dynamic)
+}
+
+// Commands were checked when the story was loaded, so the only things that can go wrong
+// when they run are ones that depend on variables or the outside world (e.g. a missing file)
+
+#[derive(Debug, Clone)]
+pub struct Backspace {
+    count: Arg<usize>,
+    unit: Arg<DisplayUnit>,
+    one_by_one: bool,
+    pace: Arg<Duration>,
+}
+
+impl StoryCommand for Backspace {
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        Ok(Backspace {
+            count: parse_arg(func, args, 0)?,
+            unit: parse_arg(func, args, 1)?,
+            one_by_one: flag(args, 2, "one_by_one"),
+            pace: parse_opt_arg(func, args, 3)?.unwrap_or(Arg::Fixed(Duration::from_millis(250))),
+        })
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, buf: &mut TermBuffer) -> Result<Transition> {
+        let (num, unit) = (st.eval(&self.count)?, st.eval(&self.unit)?);
+        if self.one_by_one {
+            let pace = st.eval(&self.pace)?;
+            Ok(Transition::Backspacing(Backspacing { unit, num, pace }))
+        } else {
+            commands::backspace(num, unit, buf);
+            Ok(Transition::Nothing)
+        }
+    }
+    fn dynamic_args(&self) -> Vec<&str> {
+        let args = vec![
+            self.count.dynamic(),
+            self.unit.dynamic(),
+            self.pace.dynamic(),
+        ];
+        args.into_iter().flatten().collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DisplayImg {
+    pub(crate) path: Arg<String>,
+    in_term: bool, // as opposed to ascii art
+}
+
+impl StoryCommand for DisplayImg {
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        Ok(DisplayImg {
+            path: parse_arg(func, args, 0)?,
+            in_term: flag(args, 1, "term"),
+        })
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, buf: &mut TermBuffer) -> Result<Transition> {
+        let path = st.get_full_path(&st.eval(&self.path)?);
+        if self.in_term {
+            img_to_term(path, buf)?;
+        } else {
+            img_to_ascii(path, buf)?;
+        }
+        Ok(Transition::Nothing)
+    }
+    fn dynamic_args(&self) -> Vec<&str> {
+        self.path.dynamic().into_iter().collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PromptYesNo {
+    var: Arg<String>,
+    default: Option<Arg<String>>,
+}
+
+impl StoryCommand for PromptYesNo {
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        Ok(PromptYesNo {
+            var: parse_arg(func, args, 0)?,
+            default: parse_opt_arg(func, args, 1)?,
+        })
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, buf: &mut TermBuffer) -> Result<Transition> {
+        let default = self.default.as_ref().map(|def| st.eval(def)).transpose()?;
+        let var = st.eval(&self.var)?;
+        st.remember_decision(buf);
+        buf.write_text(" (y/n) ");
+        let prompt = PromptingYesNo::new(var, default);
+        Ok(Transition::PromptingYesNo(prompt))
+    }
+    fn dynamic_args(&self) -> Vec<&str> {
+        opt(&self.default).into_iter().collect()
+    }
+    fn var_set(&self) -> Option<&Arg<String>> {
+        Some(&self.var)
+    }
+}
+
+// With no section for the false case, or one that doesn't exist, the story just keeps going
+fn jump_to_either(
+    st: &mut StoryTeller<Telling>,
+    cond: bool,
+    then: &Arg<String>,
+    otherwise: Option<&Arg<String>>,
+) -> Result<Transition> {
+    let sect = if cond { Some(then) } else { otherwise };
+    let sect = sect.map(|sect| st.eval(sect)).transpose()?;
+    st.story.jump_to_section(sect.as_ref());
+    Ok(Transition::Nothing)
+}
+
+// jump_if_eq, jump_if_ne, jump_if_lt and jump_if_gt
+#[derive(Debug, Clone)]
+pub struct JumpIf {
+    pub(crate) cmp: Comparison,
+    pub(crate) lhs: Arg<String>,
+    pub(crate) rhs: Arg<String>,
+    pub(crate) then: Arg<String>,
+    pub(crate) otherwise: Option<Arg<String>>, // just keep going if this is None
+}
+
+impl StoryCommand for JumpIf {
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        let cmp = match func {
+            "jump_if_eq" => Comparison::Eq,
+            "jump_if_ne" => Comparison::Ne,
+            "jump_if_lt" => Comparison::Lt,
+            _ => Comparison::Gt,
+        };
+        if matches!(cmp, Comparison::Lt | Comparison::Gt) {
+            // Only numbers can be less or greater than each other
+            for i in 0..2 {
+                parse_arg::<i64>(func, args, i)?;
+            }
+        }
+        Ok(JumpIf {
+            cmp,
+            lhs: parse_arg(func, args, 0)?,
+            rhs: parse_arg(func, args, 1)?,
+            then: parse_arg(func, args, 2)?,
+            otherwise: parse_opt_arg(func, args, 3)?,
+        })
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, _: &mut TermBuffer) -> Result<Transition> {
+        let (lhs, rhs) = (st.eval(&self.lhs)?, st.eval(&self.rhs)?);
+        let cond = match self.cmp {
+            Comparison::Eq => lhs == rhs,
+            Comparison::Ne => lhs != rhs,
+            Comparison::Lt => compare_numbers(&lhs, &rhs)?.is_lt(),
+            Comparison::Gt => compare_numbers(&lhs, &rhs)?.is_gt(),
+        };
+        jump_to_either(st, cond, &self.then, self.otherwise.as_ref())
+    }
+    fn dynamic_args(&self) -> Vec<&str> {
+        let args = vec![
+            self.lhs.dynamic(),
+            self.rhs.dynamic(),
+            self.then.dynamic(),
+            opt(&self.otherwise),
+        ];
+        args.into_iter().flatten().collect()
+    }
+    fn jump_targets(&self) -> Vec<&Arg<String>> {
+        std::iter::once(&self.then).chain(&self.otherwise).collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JumpIfTimeBetween {
+    pub(crate) start: Arg<NaiveTime>,
+    pub(crate) end: Arg<NaiveTime>,
+    pub(crate) then: Arg<String>,
+    pub(crate) otherwise: Option<Arg<String>>,
+}
+
+impl StoryCommand for JumpIfTimeBetween {
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        Ok(JumpIfTimeBetween {
+            start: parse_arg(func, args, 0)?,
+            end: parse_arg(func, args, 1)?,
+            then: parse_arg(func, args, 2)?,
+            otherwise: parse_opt_arg(func, args, 3)?,
+        })
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, _: &mut TermBuffer) -> Result<Transition> {
+        let now = st.clock().now().time();
+        let cond = time_between(now, st.eval(&self.start)?, st.eval(&self.end)?);
+        jump_to_either(st, cond, &self.then, self.otherwise.as_ref())
+    }
+    fn dynamic_args(&self) -> Vec<&str> {
+        let args = vec![
+            self.start.dynamic(),
+            self.end.dynamic(),
+            self.then.dynamic(),
+            opt(&self.otherwise),
+        ];
+        args.into_iter().flatten().collect()
+    }
+    fn jump_targets(&self) -> Vec<&Arg<String>> {
+        std::iter::once(&self.then).chain(&self.otherwise).collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JumpToFile {
+    pub(crate) file: Arg<String>,
+    pub(crate) section: Arg<String>,
+}
+
+impl StoryCommand for JumpToFile {
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        Ok(JumpToFile {
+            file: parse_arg(func, args, 0)?,
+            // Start at the beginning unless told otherwise
+            section: parse_opt_arg(func, args, 1)?.unwrap_or_else(|| Arg::Fixed("0".to_owned())),
+        })
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, _: &mut TermBuffer) -> Result<Transition> {
+        let (file, sect) = (st.eval(&self.file)?, st.eval(&self.section)?);
+        let mut story = st.story.load_neighbor(&file)?;
+        if story.start_at_section(&sect) {
+            st.story = story;
+            Ok(Transition::Nothing)
+        } else {
+            let msg = format!("'{}' has no section named '{}'", file, sect);
+            Err(RTError::InvalidInput(msg))
+        }
+    }
+    fn dynamic_args(&self) -> Vec<&str> {
+        let args = vec![self.file.dynamic(), self.section.dynamic()];
+        args.into_iter().flatten().collect()
+    }
+}
+
+// return comes back to just after this
+#[derive(Debug, Clone)]
+pub struct Goto(pub(crate) Arg<String>);
+
+impl StoryCommand for Goto {
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        Ok(Goto(parse_arg(func, args, 0)?))
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, _: &mut TermBuffer) -> Result<Transition> {
+        let sect = st.eval(&self.0)?;
+        if st.story.goto(&sect) {
+            Ok(Transition::Nothing)
+        } else {
+            Err(RTError::NoSuchSection(sect))
+        }
+    }
+    fn dynamic_args(&self) -> Vec<&str> {
+        self.0.dynamic().into_iter().collect()
+    }
+    fn jump_targets(&self) -> Vec<&Arg<String>> {
+        vec![&self.0]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Return;
+
+impl StoryCommand for Return {
+    fn parse(_: &'static str, _: &[String]) -> Result<Self> {
+        Ok(Return)
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, _: &mut TermBuffer) -> Result<Transition> {
+        if st.story.return_to_caller() {
+            Ok(Transition::Nothing)
+        } else {
+            Err(RTError::NothingToReturnTo)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EndStory;
+
+impl StoryCommand for EndStory {
+    fn parse(_: &'static str, _: &[String]) -> Result<Self> {
+        Ok(EndStory)
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, _: &mut TermBuffer) -> Result<Transition> {
+        st.story.end_story();
+        Ok(Transition::Nothing)
+    }
+}
+
+// Whether the section this is in goes on to the next one when it's done. This is part of
+// how the story is laid out, so it's worked out when the story loads and does nothing later.
+#[derive(Debug, Clone)]
+pub struct Fallthrough(pub(crate) bool);
+
+impl StoryCommand for Fallthrough {
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        match parse_arg(func, args, 0)? {
+            Arg::Fixed(yes) => Ok(Fallthrough(yes)),
+            Arg::Dynamic { arg, .. } => Err(bad_arg(func, 1, Expected::Fixed, &arg)),
+        }
+    }
+    fn run(&self, _: &mut StoryTeller<Telling>, _: &mut TermBuffer) -> Result<Transition> {
+        Ok(Transition::Nothing)
+    }
+}
+
+// (section, how likely it is compared to the others)
+#[derive(Debug, Clone)]
+pub struct JumpRandom {
+    pub(crate) choices: Vec<(Arg<String>, u32)>,
+    weights: WeightedIndex<u32>,
+}
+
+impl StoryCommand for JumpRandom {
+    // jump_random takes either just sections or sections each followed by a weight. It's
+    // weighted when every other argument is a whole number, so numbered sections need weights
+    // (even if they're all 1) to not be mistaken for them.
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        let weights: Option<Vec<u32>> = if args.len().is_multiple_of(2) {
+            args.iter()
+                .skip(1)
+                .step_by(2)
+                .map(|w| w.parse().ok())
+                .collect()
+        } else {
+            None
+        };
+        let (sects, weights): (Vec<_>, _) = match weights {
+            Some(weights) => ((0..args.len()).step_by(2).collect(), weights),
+            None => ((0..args.len()).collect(), vec![1; args.len()]),
+        };
+        let choices = sects
+            .into_iter()
+            .zip(weights.iter().copied())
+            .map(|(i, w)| Ok((parse_arg(func, args, i)?, w)))
+            .collect::<Result<_>>()?;
+        // Only fails if they're all 0 (which only weighted ones can be)
+        let weights = WeightedIndex::new(weights)
+            .map_err(|_| bad_arg(func, 2, Expected::Weight, &args[1]))?;
+        Ok(JumpRandom { choices, weights })
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, _: &mut TermBuffer) -> Result<Transition> {
+        let idx = self.weights.sample(&mut st.rng);
+        let sect = st.eval(&self.choices[idx].0)?;
+        if st.story.find_section(&sect).is_some() {
+            st.story.jump_to_section(Some(&sect));
+            Ok(Transition::Nothing)
+        } else {
+            Err(RTError::NoSuchSection(sect))
+        }
+    }
+    fn dynamic_args(&self) -> Vec<&str> {
+        self.choices
+            .iter()
+            .filter_map(|(sect, _)| sect.dynamic())
+            .collect()
+    }
+    fn jump_targets(&self) -> Vec<&Arg<String>> {
+        self.choices.iter().map(|(sect, _)| sect).collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Pause(Arg<Duration>);
+
+impl StoryCommand for Pause {
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        Ok(Pause(parse_arg(func, args, 0)?))
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, _: &mut TermBuffer) -> Result<Transition> {
+        Ok(Transition::sleep(st.eval(&self.0)?))
+    }
+    fn dynamic_args(&self) -> Vec<&str> {
+        self.0.dynamic().into_iter().collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ForceInput(Arg<Forceable>);
+
+impl StoryCommand for ForceInput {
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        Ok(ForceInput(parse_arg(func, args, 0)?))
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, _: &mut TermBuffer) -> Result<Transition> {
+        let Forceable(text) = st.eval(&self.0)?;
+        Ok(Transition::ForcingInput(ForcingInput::new(text)))
+    }
+    fn dynamic_args(&self) -> Vec<&str> {
+        self.0.dynamic().into_iter().collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChoiceMenu {
+    var: Arg<String>,
+    choices: Vec<Arg<String>>,
+}
+
+impl StoryCommand for ChoiceMenu {
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        Ok(ChoiceMenu {
+            var: parse_arg(func, args, 0)?,
+            choices: (1..args.len())
+                .map(|i| parse_arg(func, args, i))
+                .collect::<Result<_>>()?,
+        })
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, buf: &mut TermBuffer) -> Result<Transition> {
+        let choices = self
+            .choices
+            .iter()
+            .map(|choice| st.eval(choice))
+            .collect::<Result<Vec<_>>>()?;
+        let var = st.eval(&self.var)?;
+        st.remember_decision(buf);
+        let menu = ChoosingFromMenu::new(var, choices);
+        Ok(Transition::ChoosingFromMenu(menu))
+    }
+    fn dynamic_args(&self) -> Vec<&str> {
+        self.choices.iter().filter_map(Arg::dynamic).collect()
+    }
+    fn var_set(&self) -> Option<&Arg<String>> {
+        Some(&self.var)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WaitKb;
+
+impl StoryCommand for WaitKb {
+    fn parse(_: &'static str, _: &[String]) -> Result<Self> {
+        Ok(WaitKb)
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, _: &mut TermBuffer) -> Result<Transition> {
+        Ok(st.wait_kb())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MoveCursorBack(Arg<isize>);
+
+impl StoryCommand for MoveCursorBack {
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        Ok(MoveCursorBack(parse_arg(func, args, 0)?))
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, buf: &mut TermBuffer) -> Result<Transition> {
+        buf.move_cursor(-st.eval(&self.0)?);
+        Ok(Transition::Nothing)
+    }
+    fn dynamic_args(&self) -> Vec<&str> {
+        self.0.dynamic().into_iter().collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClearScreen;
+
+impl StoryCommand for ClearScreen {
+    fn parse(_: &'static str, _: &[String]) -> Result<Self> {
+        Ok(ClearScreen)
+    }
+    fn run(&self, _: &mut StoryTeller<Telling>, _: &mut TermBuffer) -> Result<Transition> {
+        utils::clear_screen();
+        Ok(Transition::Nothing)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Repeat {
+    text: Arg<String>,
+    count: Arg<usize>,
+    pace: Arg<Duration>,
+}
+
+impl StoryCommand for Repeat {
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        Ok(Repeat {
+            text: parse_arg(func, args, 0)?,
+            count: parse_arg(func, args, 1)?,
+            pace: parse_arg(func, args, 2)?,
+        })
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, _: &mut TermBuffer) -> Result<Transition> {
+        Ok(Transition::Repeating(Repeating {
+            text: st.eval(&self.text)?,
+            num: st.eval(&self.count)?,
+            pace: st.eval(&self.pace)?,
+        }))
+    }
+    fn dynamic_args(&self) -> Vec<&str> {
+        let args = vec![
+            self.text.dynamic(),
+            self.count.dynamic(),
+            self.pace.dynamic(),
+        ];
+        args.into_iter().flatten().collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RandomWordGenerator(Arg<String>);
+
+impl StoryCommand for RandomWordGenerator {
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        Ok(RandomWordGenerator(parse_arg(func, args, 0)?))
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, _: &mut TermBuffer) -> Result<Transition> {
+        let kind = st.eval(&self.0)?.to_ascii_lowercase();
+        let origin = st.story.curr_origin();
+        Ok(Transition::FetchingWord(FetchingWord::start(kind, origin)))
+    }
+    fn dynamic_args(&self) -> Vec<&str> {
+        self.0.dynamic().into_iter().collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Set {
+    var: Arg<String>,
+    val: Arg<String>,
+}
+
+impl StoryCommand for Set {
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        Ok(Set {
+            var: parse_arg(func, args, 0)?,
+            val: parse_arg(func, args, 1)?,
+        })
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, _: &mut TermBuffer) -> Result<Transition> {
+        let (var, val) = (st.eval(&self.var)?, st.eval(&self.val)?);
+        st.set_val(var, val);
+        Ok(Transition::Nothing)
+    }
+    fn dynamic_args(&self) -> Vec<&str> {
+        self.val.dynamic().into_iter().collect()
+    }
+    fn var_set(&self) -> Option<&Arg<String>> {
+        Some(&self.var)
+    }
+}
+
+// add, and incr and decr (which are just adding 1 and -1)
+#[derive(Debug, Clone)]
+pub struct Add {
+    var: Arg<String>,
+    amount: Arg<i64>,
+}
+
+impl StoryCommand for Add {
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        let amount = match func {
+            "incr" => Arg::Fixed(1),
+            "decr" => Arg::Fixed(-1),
+            _ => parse_arg(func, args, 1)?,
+        };
+        let var = parse_arg(func, args, 0)?;
+        Ok(Add { var, amount })
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, _: &mut TermBuffer) -> Result<Transition> {
+        let (var, amount) = (st.eval(&self.var)?, st.eval(&self.amount)?);
+        let val = add_to(&var, st.env.get(&var).map(String::as_str), amount)?;
+        st.set_val(var, val.to_string());
+        Ok(Transition::Nothing)
+    }
+    fn dynamic_args(&self) -> Vec<&str> {
+        self.amount.dynamic().into_iter().collect()
+    }
+    fn var_set(&self) -> Option<&Arg<String>> {
+        Some(&self.var)
+    }
+}
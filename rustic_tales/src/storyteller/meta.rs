@@ -0,0 +1,126 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use humantime::{format_duration, parse_duration};
+
+use script::token::header;
+
+use crate::commands::story_command::ArgType;
+use crate::err::{RTError, Result};
+use crate::options::{STOptions, ScrollRate};
+
+// Everything a story can say about itself in the header at the top of its file, e.g.
+// {{{
+// title : The Cave
+// author : NivenT
+// blurb : Ukuth has been wandering this cave for a long time.
+// content_warnings : death |,| grief
+// length : 10min
+// min_term_size : 100x40
+// scroll_rate : Lines(4)
+// fallthrough : yes
+// }}}
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StoryMeta {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub blurb: Option<String>,
+    pub content_warnings: Vec<String>,
+    pub length: Option<Duration>, // roughly how long the story takes to read
+    pub min_term_size: Option<(u16, u16)>, // (columns, rows)
+    pub scroll_rate: Option<ScrollRate>, // uses the same syntax as options.ron
+    pub fallthrough: Option<bool>, // whether sections go on to the next one (no by default)
+}
+
+impl StoryMeta {
+    pub fn from_fields(fields: &[(String, String)]) -> Result<StoryMeta> {
+        let mut meta = StoryMeta::default();
+        for (key, val) in fields {
+            match key.as_str() {
+                "title" => meta.title = Some(val.clone()),
+                "author" => meta.author = Some(val.clone()),
+                "blurb" => meta.blurb = Some(val.clone()),
+                "content_warnings" => {
+                    meta.content_warnings = val
+                        .split("|,|")
+                        .map(str::trim)
+                        .filter(|w| !w.is_empty())
+                        .map(str::to_owned)
+                        .collect()
+                }
+                "length" => meta.length = Some(parse_duration(val)?),
+                "min_term_size" => {
+                    let size = val
+                        .split_once('x')
+                        .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)));
+                    match size {
+                        Some(size) => meta.min_term_size = Some(size),
+                        None => {
+                            return Err(RTError::InvalidInput(format!(
+                                "min_term_size should look like 'COLSxROWS', not '{}'",
+                                val
+                            )))
+                        }
+                    }
+                }
+                "scroll_rate" => meta.scroll_rate = Some(ron::de::from_str(val)?),
+                "fallthrough" => match bool::from_arg(val) {
+                    Some(yes) => meta.fallthrough = Some(yes),
+                    None => {
+                        return Err(RTError::InvalidInput(format!(
+                            "fallthrough should be 'yes' or 'no', not '{}'",
+                            val
+                        )))
+                    }
+                },
+                _ => {
+                    return Err(RTError::InvalidInput(format!(
+                        "stories do not have a '{}' in their header",
+                        key
+                    )))
+                }
+            }
+        }
+        Ok(meta)
+    }
+    // Only reads the header, so this is cheap even for long stories
+    pub fn from_file(path: impl AsRef<Path>) -> Result<StoryMeta> {
+        let source = fs::read_to_string(path)?;
+        match header(&source) {
+            Some(fields) => StoryMeta::from_fields(&fields),
+            None => Ok(StoryMeta::default()),
+        }
+    }
+
+    // Whether there's anything to tell the reader before the story starts (some things, like
+    // the fallthrough policy, are just for the storyteller)
+    pub fn has_intro(&self) -> bool {
+        self.title.is_some()
+            || self.author.is_some()
+            || self.blurb.is_some()
+            || !self.content_warnings.is_empty()
+            || self.min_term_size.is_some()
+    }
+    // What to call the story in menus
+    pub fn label(&self, file_name: &str) -> String {
+        let mut label = self.title.clone().unwrap_or_else(|| file_name.to_owned());
+        if let Some(author) = &self.author {
+            label += &format!(" by {}", author);
+        }
+        if let Some(length) = self.length {
+            label += &format!(" (about {})", format_duration(length));
+        }
+        label
+    }
+    // The reader's options, with whatever the story would like changed (if the reader lets it)
+    pub fn apply_to(&self, opts: &STOptions) -> STOptions {
+        let mut opts = opts.clone();
+        if opts.allow_story_overrides {
+            if let Some(rate) = self.scroll_rate {
+                opts.scroll_rate = rate;
+            }
+        }
+        opts
+    }
+}
@@ -1,7 +1,10 @@
+pub mod builtins;
+pub mod meta;
 pub mod story;
 mod storyteller_base;
 mod storyteller_states;
 mod unit;
 
-pub use self::storyteller_base::{SnippetInfo, StoryTeller};
+pub use self::storyteller_base::StoryTeller;
 pub use self::storyteller_states::*;
+pub use self::unit::Unit;
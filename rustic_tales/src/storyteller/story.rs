@@ -1,12 +1,21 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+use serde::{Deserialize, Serialize};
 use terminal_size::{terminal_size, Height, Width};
+use unicode_segmentation::UnicodeSegmentation;
 
-use script::token::{tokenize, Token};
+use script::token::{tokenize_spanned, Pos, Token, TokenStream};
 
-use crate::err::{RTError, Result};
-use crate::options::DisplayUnit;
+use crate::commands::registry::Arity;
+use crate::commands::story_command::Arg;
+use crate::err::{RTError, Result, SourceLoc};
+use crate::options::{BufOptions, DisplayUnit};
 
+use super::builtins::Fallthrough;
+use super::meta::StoryMeta;
 use super::unit::Unit;
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -41,7 +50,7 @@ impl Line {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Page {
     lines: Vec<Line>,
 }
@@ -50,9 +59,6 @@ impl Page {
     pub fn new() -> Page {
         Page { lines: Vec::new() }
     }
-    pub fn max_page_len() -> usize {
-        Page::max_page_height() * Line::max_line_len()
-    }
     pub fn max_page_height() -> usize {
         // Leave a couple lines open at the end to say 'Next page...'
         if let Some((_, Height(h))) = terminal_size() {
@@ -62,30 +68,47 @@ impl Page {
         }
     }
 
-    fn area_to_len((w, h): (usize, usize)) -> usize {
-        w + h * Line::max_line_len()
+    fn area_to_len((w, h): (usize, usize), line_len: usize) -> usize {
+        w + h * line_len
     }
     // Returns number of units in this page
-    fn extract_page(units: &[Unit], offset: usize) -> (Page, usize) {
+    fn extract_page(units: &[Unit], offset: usize, tab_width: usize) -> (Page, usize) {
+        // Asking the terminal for its size for every unit is slow when there are a lot of units
+        let (line_len, page_height) = (Line::max_line_len(), Page::max_page_height());
+        Page::fill_page(units, offset, line_len, page_height, tab_width)
+    }
+    // Line lengths are in terminal columns (see Unit::area), not chars
+    fn fill_page(
+        units: &[Unit],
+        offset: usize,
+        line_len: usize,
+        page_height: usize,
+        tab_width: usize,
+    ) -> (Page, usize) {
         let mut page = Page::new();
 
         let mut idx = 0;
+        // a word too long for a line still gets one, it just wraps onto the rows after it
+        let mut rows = 0;
         loop {
             let mut curr_line = Line::new(idx + offset);
+            let mut width = 0;
             curr_line.len = units[idx..]
                 .iter()
-                .scan(0, |len, next| {
-                    if *len >= Line::max_line_len() || next.is_page_end() || next.is_sect_start() {
+                .scan(&mut width, |len, next| {
+                    if **len >= line_len || next.is_page_end() || next.is_sect_start() {
                         None
                     } else if next.is_newline() {
-                        *len += Page::area_to_len(next.area());
+                        // skips to the end of whatever row the line is on
+                        **len = (**len).max(1).next_multiple_of(line_len);
                         Some(next)
                     } else {
-                        let unit_size = Page::area_to_len(next.area());
-                        if *len + unit_size > Line::max_line_len() {
+                        let area = next.area(**len % line_len, tab_width);
+                        let unit_size = Page::area_to_len(area, line_len);
+                        if **len > 0 && **len + unit_size > line_len {
                             None
                         } else {
-                            *len += unit_size;
+                            **len += unit_size;
                             Some(next)
                         }
                     }
@@ -93,11 +116,12 @@ impl Page {
                 .count();
             if curr_line.len != 0 {
                 idx += curr_line.len;
+                rows += width.div_ceil(line_len).max(1);
                 page.lines.push(curr_line);
             }
             if idx >= units.len()
-                || page.lines.len() >= Page::max_page_height()
-                || idx >= Page::max_page_len()
+                || rows >= page_height
+                || idx >= page_height * line_len
                 || units[idx].is_sect_start()
             {
                 break;
@@ -123,15 +147,21 @@ impl Page {
 pub struct Section {
     pages: Vec<Page>,
     name: String,
+    fallthrough: bool, // whether the next section starts when this one ends
 }
 
 impl Section {
     // Returns number of units in this page
-    fn extract_section(name: &str, units: &[Unit], offset: usize) -> (Section, usize) {
+    fn extract_section(
+        name: &str,
+        units: &[Unit],
+        offset: usize,
+        tab_width: usize,
+    ) -> (Section, usize) {
         let mut pages = Vec::new();
         let mut idx = 0;
         while idx < units.len() && !units[idx].is_sect_start() {
-            let (page, offset) = Page::extract_page(&units[idx..], offset + idx);
+            let (page, offset) = Page::extract_page(&units[idx..], offset + idx, tab_width);
             if offset > 0 {
                 if !page.lines.is_empty() {
                     pages.push(page);
@@ -144,6 +174,7 @@ impl Section {
         let sect = Section {
             pages,
             name: name.to_owned(),
+            fallthrough: false,
         };
         (sect, idx)
     }
@@ -156,6 +187,16 @@ impl Section {
     pub fn get_name(&self) -> &String {
         &self.name
     }
+    pub fn falls_through(&self) -> bool {
+        self.fallthrough
+    }
+    // Every index into the story's contents that is part of this section, in order
+    pub fn unit_idxes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.pages
+            .iter()
+            .flat_map(|page| page.lines.iter())
+            .flat_map(|line| line.get_start()..=line.get_end())
+    }
     pub fn start_idx(&self) -> Option<usize> {
         self.pages
             .first()
@@ -190,9 +231,89 @@ pub enum Span {
     BlockingCommand,
 }
 
+// Where a reader is, written down so they can come back to it later. Bookmarks depend on how
+// the story got split into pages (i.e. on the size of the terminal), so this uses indices into
+// the story's contents instead.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedPlace {
+    sect: usize,
+    unit: usize,
+    letter: usize,
+    callers: Vec<(usize, usize)>, // (section, unit) of each goto that hasn't returned yet
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 pub struct StoryFlags {
     just_changed_section: bool,
+    ended: bool, // by end_story, as opposed to running out of story
+}
+
+// Where a unit came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Origin {
+    pub file: usize, // index into the story's files
+    pub pos: Pos,
+}
+
+#[derive(Debug, Clone)]
+struct SourceFile {
+    name: String,         // usually a path
+    text: Option<String>, // None if it gets read from name instead
+    hash: u64,            // see Story::hash
+}
+
+impl SourceFile {
+    // A file that gets read (and hashed) as it's tokenized
+    fn unread(name: String) -> SourceFile {
+        SourceFile {
+            name,
+            text: None,
+            hash: FNV_START,
+        }
+    }
+    fn from_text(name: &str, text: &str) -> SourceFile {
+        SourceFile {
+            name: name.to_owned(),
+            text: Some(text.to_owned()),
+            hash: fnv1a(FNV_START, text.as_bytes()),
+        }
+    }
+}
+
+// FNV-1a, since std's hasher is allowed to change between versions of Rust
+const FNV_START: u64 = 0xcbf29ce484222325;
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// Hashes a file as it's read, so it doesn't have to be kept around to be hashed later
+struct Hashing<'a, R> {
+    reader: R,
+    hash: &'a mut u64,
+}
+
+impl<R: BufRead> Read for Hashing<'_, R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(out)?;
+        *self.hash = fnv1a(*self.hash, &out[..n]);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Hashing<'_, R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.reader.fill_buf()
+    }
+    fn consume(&mut self, amt: usize) {
+        // What's being consumed was already read into the buffer, so this doesn't read anything
+        if let Ok(buf) = self.reader.fill_buf() {
+            *self.hash = fnv1a(*self.hash, &buf[..amt]);
+        }
+        self.reader.consume(amt);
+    }
 }
 
 // Instead of directly printing everything, should there be a buffer keeping better track of words and whatnot?
@@ -200,23 +321,78 @@ pub struct StoryFlags {
 pub struct Story {
     sections: Vec<Section>,
     contents: Vec<Unit>,
+    locs: Vec<Origin>,      // where each unit of contents came from
+    files: Vec<SourceFile>, // the story itself, followed by any files it includes
+    meta: StoryMeta,
     place: Bookmark,
     curr_sect_idx: usize,
     flags: StoryFlags,
+    callers: Vec<(usize, Bookmark)>, // where each goto that hasn't returned yet was
+    tab_width: usize,                // what the pages were measured with (see BufOptions)
 }
 
 impl FromStr for Story {
     type Err = RTError;
 
     fn from_str(s: &str) -> Result<Self> {
-        let tkns = tokenize(s);
-        let contents: Vec<_> = tkns
-            .into_iter()
-            // This is embaressing. It was `filter_map`, not `flat_map` (see 0f14fcf6473f458a919c0876e13e3972f0726b0f)
-            .flat_map(Unit::from_token)
-            .filter(|u| !u.is_empty())
-            .collect();
-        let contents = Story::prepare_contents(contents);
+        // Without a file, the best we can do is look for included files in the current directory
+        let tab_width = BufOptions::default().tab_width;
+        let file = SourceFile::from_text("<story>", s);
+        Story::parse(file, Path::new("."), Vec::new(), tab_width)
+    }
+}
+
+impl Story {
+    // tab_width is how many columns apart tab stops are (see BufOptions), since that changes
+    // how much fits on a page
+    pub fn from_file(path: impl AsRef<Path>, tab_width: usize) -> Result<Story> {
+        let path = path.as_ref();
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let including = vec![path.canonicalize()?];
+        let file = SourceFile::unread(path.display().to_string());
+        Story::parse(file, dir, including, tab_width)
+    }
+    // Loads a story from a file next to this one (e.g. the next chapter)
+    pub fn load_neighbor(&self, file: &str) -> Result<Story> {
+        let dir = Path::new(&self.files[0].name)
+            .parent()
+            .unwrap_or_else(|| Path::new("."));
+        Story::from_file(dir.join(file), self.tab_width)
+    }
+
+    // dir is where included files are looked for, and including is every file currently
+    // being parsed (so include cycles can be caught)
+    fn parse(
+        file: SourceFile,
+        dir: &Path,
+        mut including: Vec<PathBuf>,
+        tab_width: usize,
+    ) -> Result<Story> {
+        let mut files = vec![file];
+        let tkns = Story::tokenize_file(0, dir, &mut files, &mut including)?;
+        // Included files can have headers too, but only the story's own one counts
+        let meta = match tkns.first() {
+            Some((Token::Header(fields), origin)) if origin.file == 0 => {
+                StoryMeta::from_fields(fields).map_err(|e| Story::locate_in(&files, *origin, e))?
+            }
+            _ => StoryMeta::default(),
+        };
+        // Keep going after a bad command so the author hears about all of them at once
+        let mut errs = Vec::new();
+        let mut contents = Vec::with_capacity(tkns.len());
+        for (tkn, origin) in tkns {
+            match Unit::from_token(tkn, origin.pos) {
+                Ok(units) => contents.extend(
+                    units
+                        .into_iter()
+                        .filter(|(u, _)| !u.is_empty())
+                        .map(|(unit, pos)| (unit, Origin { pos, ..origin })),
+                ),
+                Err(e) => errs.push((origin, e)),
+            }
+        }
+        let (contents, locs): (Vec<_>, Vec<_>) =
+            Story::prepare_contents(contents).into_iter().unzip();
 
         let mut sects = Vec::new();
         let mut idx = 0;
@@ -226,7 +402,8 @@ impl FromStr for Story {
                 name = sname;
                 idx += 1;
             } else {
-                let (sect, offset) = Section::extract_section(name, &contents[idx..], idx);
+                let (sect, offset) =
+                    Section::extract_section(name, &contents[idx..], idx, tab_width);
                 if offset > 0 {
                     if !sect.pages.is_empty() {
                         sects.push(sect);
@@ -236,17 +413,113 @@ impl FromStr for Story {
             }
         }
 
+        for sect in sects.iter_mut() {
+            let policy = sect.unit_idxes().filter_map(|idx| match &contents[idx] {
+                Unit::Command(call, _) => call.cmd.downcast_ref::<Fallthrough>().map(|f| f.0),
+                _ => None,
+            });
+            sect.fallthrough = policy.last().or(meta.fallthrough).unwrap_or(false);
+        }
+
+        // Jumps to sections that don't exist can be caught now, unless they depend on variables
+        for (unit, origin) in contents.iter().zip(&locs) {
+            if let Unit::Command(call, _) = unit {
+                for sect in call.cmd.jump_targets().into_iter().filter_map(Arg::fixed) {
+                    if Story::find_section_in(&sects, sect).is_none() {
+                        errs.push((*origin, RTError::NoSuchSection(sect.clone())));
+                    }
+                }
+            }
+        }
+        errs.sort_by_key(|(origin, _)| (origin.file, origin.pos));
+        let mut errs: Vec<_> = errs
+            .into_iter()
+            .map(|(origin, e)| Story::locate_in(&files, origin, e))
+            .collect();
+        match errs.len() {
+            0 => {}
+            1 => return Err(errs.remove(0)),
+            _ => return Err(RTError::Several(errs)),
+        }
+
         Ok(Story {
             sections: sects,
             contents,
+            locs,
+            files,
+            meta,
             place: Bookmark::default(),
             curr_sect_idx: 0,
             flags: StoryFlags::default(),
+            callers: Vec::new(),
+            tab_width,
         })
     }
-}
+    // Tokenizes files[file], splicing in the tokens of any files it includes
+    fn tokenize_file(
+        file: usize,
+        dir: &Path,
+        files: &mut Vec<SourceFile>,
+        including: &mut Vec<PathBuf>,
+    ) -> Result<Vec<(Token, Origin)>> {
+        let tkns = match &files[file].text {
+            Some(text) => tokenize_spanned(text),
+            None => {
+                let mut hash = FNV_START;
+                let reader = Hashing {
+                    reader: BufReader::new(File::open(&files[file].name)?),
+                    hash: &mut hash,
+                };
+                let tkns = TokenStream::new(reader).collect::<io::Result<Vec<_>>>()?;
+                files[file].hash = hash;
+                tkns
+            }
+        };
+        let mut ret = Vec::with_capacity(tkns.len());
+        for (tkn, pos) in tkns {
+            let origin = Origin { file, pos };
+            match tkn {
+                Token::Command(func, args, _) if func == "include" => {
+                    // errors deeper in the chain of includes already know where they are
+                    let included =
+                        Story::include(&args, dir, files, including).map_err(|e| match e {
+                            RTError::AtPos(..) => e,
+                            e => Story::locate_in(files, origin, e),
+                        })?;
+                    ret.extend(included);
+                }
+                tkn => ret.push((tkn, origin)),
+            }
+        }
+        Ok(ret)
+    }
+    fn include(
+        args: &[String],
+        dir: &Path,
+        files: &mut Vec<SourceFile>,
+        including: &mut Vec<PathBuf>,
+    ) -> Result<Vec<(Token, Origin)>> {
+        Arity::exactly(1).check("include", args.len())?;
+        let path = dir.join(&args[0]);
+        let canon = path.canonicalize()?;
+        if including.contains(&canon) {
+            let mut cycle: Vec<_> = including
+                .iter()
+                .skip_while(|&p| p != &canon)
+                .map(|p| p.display().to_string())
+                .collect();
+            cycle.push(canon.display().to_string());
+            return Err(RTError::IncludeCycle(cycle));
+        }
+
+        files.push(SourceFile::unread(path.display().to_string()));
+        including.push(canon);
+        let dir = path.parent().unwrap_or(dir);
+        let tkns = Story::tokenize_file(files.len() - 1, dir, files, including);
+        including.pop();
+        tkns
+    }
 
-impl Story {
     fn end(&self) -> Bookmark {
         let sect = self.curr_sect();
         let last_page = sect.pages.len().saturating_sub(1);
@@ -255,7 +528,7 @@ impl Story {
 
         let last_word_idx = sect.pages[last_page].lines[last_line].start_idx + last_word;
         let last_letter = if let Unit::Word(w) = &self.contents[last_word_idx] {
-            w.chars().count().saturating_sub(1)
+            w.graphemes(true).count().saturating_sub(1)
         } else {
             0
         };
@@ -266,19 +539,47 @@ impl Story {
             letter: last_letter,
         }
     }
-    // This *might* no longer be what I want now that I've added sections
-    // This checks to see if the current section is over
-    // I *don't* want sections to automatically roll over to the next one,
-    // so I *think* this *is* what I want.
-    pub fn is_over(&self) -> bool {
+    // Whether the current place is the last thing in the current section
+    fn at_sect_end(&self) -> bool {
         self.place >= self.end()
     }
+    // The section the current one rolls over into (if it does)
+    fn next_sect(&self) -> Option<usize> {
+        let next = self.curr_sect_idx + 1;
+        (self.curr_sect().fallthrough && next < self.sections.len()).then_some(next)
+    }
+    // Sections only roll over into the next one if they're set up to (see Section::fallthrough)
+    pub fn is_over(&self) -> bool {
+        self.flags.ended || (self.at_sect_end() && self.next_sect().is_none())
+    }
     pub fn curr_sect(&self) -> &Section {
         &self.sections[self.curr_sect_idx]
     }
+    fn idx_of(&self, place: Bookmark) -> usize {
+        self.idx_in(self.curr_sect_idx, place)
+    }
+    fn idx_in(&self, sect: usize, place: Bookmark) -> usize {
+        let sect = &self.sections[sect];
+        sect.pages[place.page].lines[place.line].start_idx + place.word
+    }
+    // The other way around from idx_in
+    fn bookmark_of(&self, sect: usize, idx: usize) -> Option<Bookmark> {
+        self.sections
+            .get(sect)?
+            .pages
+            .iter()
+            .enumerate()
+            .flat_map(|(page, p)| p.lines.iter().enumerate().map(move |(l, line)| (page, l, line)))
+            .find(|(_, _, line)| line.start_idx <= idx && idx < line.start_idx + line.len)
+            .map(|(page, line, l)| Bookmark {
+                page,
+                line,
+                word: idx - l.start_idx,
+                letter: 0,
+            })
+    }
     pub fn get(&self, place: Bookmark) -> &Unit {
-        let sect = self.curr_sect();
-        &self.contents[sect.pages[place.page].lines[place.line].start_idx + place.word]
+        &self.contents[self.idx_of(place)]
     }
     pub fn get_curr(&self) -> &Unit {
         self.get(self.place)
@@ -288,12 +589,19 @@ impl Story {
             self.flags.just_changed_section = false;
             return Span::Section;
         }
+        if self.at_sect_end() {
+            if let Some(next) = self.next_sect() {
+                self.curr_sect_idx = next;
+                self.place.reset();
+                return Span::Section;
+            }
+        }
         // Would rather call self.curr_sect(), but then the complier seems to think
         // I'm borrowing all of self and not just one field, since things are happening
         // across function boundaries (at least, I think this is the issue)
         let sect = &self.sections[self.curr_sect_idx];
         let unit = self.get(self.place).clone(); // I really hate these clone's
-        if disp_by == DisplayUnit::Word || !unit.is_word() {
+        if !disp_by.is_char() || !unit.is_word() {
             // There's probably a more concise way to write this, but this works
             self.place.letter = 0;
             self.place.word += 1;
@@ -314,7 +622,7 @@ impl Story {
             }
         } else if let Unit::Word(w) = unit {
             self.place.letter += 1;
-            if self.place.letter == w.chars().count() {
+            if self.place.letter == w.graphemes(true).count() {
                 self.advance(DisplayUnit::Word)
             } else {
                 Span::Char
@@ -327,14 +635,7 @@ impl Story {
     pub fn jump_to_section(&mut self, sect_identifier: Option<&String>) -> bool {
         self.flags.just_changed_section = false;
         if let Some(ident) = sect_identifier {
-            let idx = ident.parse::<usize>().ok().or_else(|| {
-                self.sections
-                    .iter()
-                    .enumerate()
-                    .find(|(_, sect)| &sect.name == ident)
-                    .map(|(i, _)| i)
-            });
-            if let Some(idx) = idx {
+            if let Some(idx) = self.find_section(ident) {
                 let old_idx = self.curr_sect_idx;
                 self.curr_sect_idx = idx;
                 self.place.reset();
@@ -343,26 +644,151 @@ impl Story {
         }
         self.flags.just_changed_section
     }
+    // For a story that was just loaded (e.g. by jump_to_file), which starts at the top of the
+    // section even if it's the one the story was already on. Returns false if there's no such
+    // section.
+    pub fn start_at_section(&mut self, ident: &str) -> bool {
+        match self.find_section(ident) {
+            Some(idx) => {
+                self.curr_sect_idx = idx;
+                self.place.reset();
+                self.flags.just_changed_section = true;
+                true
+            }
+            None => false,
+        }
+    }
+    // Like jump_to_section, but return can come back here. Returns false if there's no such
+    // section.
+    pub fn goto(&mut self, sect_identifier: &String) -> bool {
+        if self.find_section(sect_identifier).is_none() {
+            return false;
+        }
+        let here = (self.curr_sect_idx, self.place);
+        self.jump_to_section(Some(sect_identifier));
+        self.callers.push(here);
+        true
+    }
+    // Goes back to just after the last goto. Returns false if there wasn't one.
+    pub fn return_to_caller(&mut self) -> bool {
+        match self.callers.pop() {
+            Some((sect, place)) => {
+                self.curr_sect_idx = sect;
+                // still on the goto, but the story advances past it next
+                self.place = place;
+                self.flags.just_changed_section = false;
+                true
+            }
+            None => false,
+        }
+    }
+    pub fn end_story(&mut self) {
+        self.flags.ended = true;
+    }
+    // A section can be identified either by its name or its index
+    pub fn find_section(&self, ident: &str) -> Option<usize> {
+        Story::find_section_in(&self.sections, ident)
+    }
+    fn find_section_in(sections: &[Section], ident: &str) -> Option<usize> {
+        ident
+            .parse::<usize>()
+            .ok()
+            .filter(|&idx| idx < sections.len())
+            .or_else(|| sections.iter().position(|sect| sect.name == ident))
+    }
     pub fn get_place(&self) -> Bookmark {
         self.place
     }
-
-    fn prepare_contents(mut contents: Vec<Unit>) -> Vec<Unit> {
-        let bad_idxes: Vec<_> = (0..contents.len() - 1)
-            .filter(|&i| {
-                let not_double = contents[i].is_newline() && !contents[i + 1].is_newline();
-                if not_double && i > 0 {
-                    // commands are a little special.
-                    !contents[i - 1].is_command()
-                } else {
-                    not_double
-                }
-            })
-            .collect();
-        for i in bad_idxes.into_iter().rev() {
-            contents.remove(i);
+    pub fn saved_place(&self) -> SavedPlace {
+        // once the story's over, the place can be past the end of it
+        let place = self.place.min(self.end());
+        SavedPlace {
+            sect: self.curr_sect_idx,
+            unit: self.idx_of(place),
+            letter: place.letter,
+            callers: self
+                .callers
+                .iter()
+                .map(|&(sect, place)| (sect, self.idx_in(sect, place)))
+                .collect(),
         }
+    }
+    // Fails if saved doesn't point anywhere in this story (e.g. it was saved from another one)
+    pub fn restore(&mut self, saved: &SavedPlace) -> Result<()> {
+        let bad_place = || RTError::InvalidInput("The save points outside the story".to_owned());
+        let mut place = self.bookmark_of(saved.sect, saved.unit).ok_or_else(bad_place)?;
+        place.letter = saved.letter;
+        if let Unit::Word(w) = &self.contents[saved.unit] {
+            if saved.letter >= w.graphemes(true).count() {
+                return Err(bad_place());
+            }
+        }
+        let callers = saved
+            .callers
+            .iter()
+            .map(|&(sect, idx)| self.bookmark_of(sect, idx).map(|place| (sect, place)))
+            .collect::<Option<_>>()
+            .ok_or_else(bad_place)?;
+
+        self.curr_sect_idx = saved.sect;
+        self.place = place;
+        self.callers = callers;
+        self.flags = StoryFlags::default();
+        Ok(())
+    }
+    // Changes whenever any of the files making up the story do, so a save can tell whether it
+    // still lines up with the story
+    pub fn hash(&self) -> u64 {
+        self.files.iter().fold(FNV_START, |hash, file| {
+            fnv1a(hash, &file.hash.to_le_bytes())
+        })
+    }
+    // Where the unit at the current place came from
+    pub fn curr_origin(&self) -> Origin {
+        self.locs[self.idx_of(self.place)]
+    }
+    // Attaches origin (and the line it is on) to e so the user knows where to look
+    pub fn locate(&self, origin: Origin, e: RTError) -> RTError {
+        Story::locate_in(&self.files, origin, e)
+    }
+    fn locate_in(files: &[SourceFile], origin: Origin, e: RTError) -> RTError {
+        let file = &files[origin.file];
+        let nth = origin.pos.line - 1;
+        let line = match &file.text {
+            Some(text) => text.lines().nth(nth).map(str::to_owned),
+            // Files aren't kept around once they're read, so this is the only time it's needed
+            None => File::open(&file.name)
+                .ok()
+                .and_then(|f| BufReader::new(f).lines().nth(nth)?.ok()),
+        };
+        let loc = SourceLoc {
+            file: file.name.clone(),
+            pos: origin.pos,
+            line: line.unwrap_or_default(),
+        };
+        RTError::AtPos(loc, Box::new(e))
+    }
+
+    fn prepare_contents(contents: Vec<(Unit, Origin)>) -> Vec<(Unit, Origin)> {
+        let is_bad = |i: usize| {
+            if i + 1 >= contents.len() {
+                return false;
+            }
+            let not_double = contents[i].0.is_newline() && !contents[i + 1].0.is_newline();
+            if not_double && i > 0 {
+                // commands are a little special.
+                !contents[i - 1].0.is_command()
+            } else {
+                not_double
+            }
+        };
+        // Removing things one at a time takes forever when a story is as long as a book
+        let keep: Vec<_> = (0..contents.len()).map(|i| !is_bad(i)).collect();
         contents
+            .into_iter()
+            .zip(keep)
+            .filter_map(|(unit, keep)| keep.then_some(unit))
+            .collect()
     }
 }
 
@@ -373,4 +799,255 @@ impl Story {
     pub fn get_sections(&self) -> &Vec<Section> {
         &self.sections
     }
+    pub fn get_locs(&self) -> &Vec<Origin> {
+        &self.locs
+    }
+    pub fn get_meta(&self) -> &StoryMeta {
+        &self.meta
+    }
+    pub fn file_name(&self, file: usize) -> &String {
+        &self.files[file].name
+    }
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::utils::scratch_stories;
+
+    fn words(text: &str) -> Vec<Unit> {
+        text.split(' ')
+            .flat_map(|w| [Unit::Word(w.to_owned()), Unit::WhiteSpace(" ".to_owned())])
+            .collect()
+    }
+
+    #[test]
+    fn wide_areas() {
+        assert_eq!(Unit::Word("日本語".to_owned()).area(0, 4), (7, 0));
+        assert_eq!(Unit::Word("cafe\u{301}".to_owned()).area(0, 4), (5, 0));
+        assert_eq!(Unit::Word("👍🏽!".to_owned()).area(0, 4), (4, 0));
+        assert_eq!(Unit::Char('猫').area(0, 4), (2, 0));
+    }
+
+    #[test]
+    fn japanese_lines() {
+        // each word + space is 8 columns, so only one fits on a line
+        let units = words("日本語 日本語 日本語");
+        let (page, len) = Page::fill_page(&units, 0, 10, 5, 4);
+        assert_eq!(len, units.len());
+        let lens: Vec<_> = page.get_lines().iter().map(|line| line.len).collect();
+        assert_eq!(lens, [2, 2, 2]);
+    }
+
+    #[test]
+    fn word_wider_than_line() {
+        // 29 columns (with the space) on a 10 column line takes up 3 rows
+        let units = words("吾輩は猫である名前はまだ無い 🐈");
+        let (page, len) = Page::fill_page(&units, 0, 10, 3, 4);
+        assert_eq!(page.get_lines().len(), 1);
+        assert_eq!(len, 1);
+
+        let (page, len) = Page::fill_page(&units, 0, 10, 4, 4);
+        assert_eq!(page.get_lines().len(), 2);
+        assert_eq!(len, units.len());
+    }
+
+    #[test]
+    fn tab_areas() {
+        let tab = Unit::WhiteSpace("\t".to_owned());
+        assert_eq!(tab.area(0, 4), (4, 0));
+        assert_eq!(tab.area(1, 4), (3, 0));
+        assert_eq!(tab.area(1, 8), (7, 0));
+        assert_eq!(Unit::WhiteSpace(" \t".to_owned()).area(2, 4), (2, 0));
+    }
+
+    #[test]
+    fn tabbed_lines() {
+        let units = [
+            Unit::WhiteSpace("\t".to_owned()),
+            Unit::Word("ab".to_owned()),
+            Unit::WhiteSpace("\t".to_owned()),
+            Unit::Word("c".to_owned()),
+        ];
+        // 4 + 3 + 1 + 2 fills the line exactly (words count the space after them)
+        let (page, _) = Page::fill_page(&units, 0, 10, 5, 4);
+        let lens: Vec<_> = page.get_lines().iter().map(|line| line.len).collect();
+        assert_eq!(lens, [4]);
+        // but with wider tabs, the first word doesn't fit after the first one
+        let (page, _) = Page::fill_page(&units, 0, 10, 5, 8);
+        let lens: Vec<_> = page.get_lines().iter().map(|line| line.len).collect();
+        assert_eq!(lens, [1, 3]);
+    }
+
+    const BRANCHING: &str = concat!(
+        "Once upon a time\n",
+        "\n",
+        "{{ goto : middle }}\n",
+        "\n",
+        "the end\n",
+        "#=$ middle $=#\n",
+        "Somewhere in the middle\n",
+        "\n",
+        "{{ return :}}\n",
+    );
+
+    #[test]
+    fn save_and_restore() {
+        let mut story: Story = BRANCHING.parse().unwrap();
+        // into the middle section, partway through the first word
+        story.goto(&"middle".to_owned());
+        story.advance(DisplayUnit::Word);
+        story.advance(DisplayUnit::Char);
+        story.advance(DisplayUnit::Char);
+        let saved = story.saved_place();
+
+        let mut resumed: Story = BRANCHING.parse().unwrap();
+        resumed.restore(&saved).unwrap();
+        assert_eq!(resumed.curr_sect().get_name(), "middle");
+        assert_eq!(resumed.get_place(), story.get_place());
+        assert_eq!(resumed.get_place().letter, 2);
+        assert_eq!(resumed.saved_place(), saved);
+        // and the goto still comes back to the first section
+        assert!(resumed.return_to_caller());
+        assert_eq!(resumed.curr_sect().get_name(), "Main Section");
+    }
+
+    #[test]
+    fn bad_saves() {
+        let mut story: Story = BRANCHING.parse().unwrap();
+        let start = story.saved_place();
+        let mut saved = story.saved_place();
+        saved.sect = 5;
+        assert!(story.restore(&saved).is_err());
+        let mut saved = story.saved_place();
+        saved.letter = 100;
+        assert!(story.restore(&saved).is_err());
+        let mut saved = story.saved_place();
+        saved.unit = story.get_contents().len();
+        assert!(story.restore(&saved).is_err());
+        // the unit has to be in the section the save says it is
+        let mut saved = story.saved_place();
+        saved.sect = 1;
+        assert!(story.restore(&saved).is_err());
+        let mut saved = story.saved_place();
+        saved.callers.push((0, story.get_contents().len()));
+        assert!(story.restore(&saved).is_err());
+        // and nothing changes when it fails
+        assert_eq!(story.saved_place(), start);
+
+        let edited: Story = BRANCHING.replace("time", "midnight").parse().unwrap();
+        assert_ne!(story.hash(), edited.hash());
+        let same: Story = BRANCHING.parse().unwrap();
+        assert_eq!(story.hash(), same.hash());
+    }
+
+    fn words_in(story: &Story) -> Vec<&str> {
+        story
+            .get_contents()
+            .iter()
+            .filter_map(|unit| match unit {
+                Unit::Word(w) => Some(w.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn include_files() {
+        let path = scratch_stories(
+            "story_include",
+            &[
+                ("main.txt", "Before\n{{ include : parts/mid.txt }}\nAfter\n"),
+                // relative to the file doing the including
+                ("parts/mid.txt", "Middle\n{{ include : end.txt }}\n"),
+                ("parts/end.txt", "End\n"),
+            ],
+        );
+        let story = Story::from_file(&path, 4).unwrap();
+        assert_eq!(words_in(&story), ["Before", "Middle", "End", "After"]);
+        // errors in included files point at the included file
+        let end = story
+            .get_contents()
+            .iter()
+            .position(|unit| matches!(unit, Unit::Word(w) if w == "End"))
+            .unwrap();
+        let origin = story.get_locs()[end];
+        assert!(story.file_name(origin.file).ends_with("parts/end.txt"));
+        assert_eq!((origin.pos.line, origin.pos.col), (1, 1));
+    }
+
+    #[test]
+    fn hash_read_files() {
+        let path = scratch_stories("story_hash", &[("story.txt", BRANCHING)]);
+        let story = Story::from_file(&path, 4).unwrap();
+        let parsed: Story = BRANCHING.parse().unwrap();
+        assert_eq!(story.hash(), parsed.hash());
+    }
+
+    #[test]
+    fn include_cycles() {
+        let path = scratch_stories(
+            "story_cycle",
+            &[
+                ("a.txt", "A\n{{ include : b.txt }}\n"),
+                ("b.txt", "B\n{{ include : a.txt }}\n"),
+            ],
+        );
+        match Story::from_file(&path, 4) {
+            Err(RTError::AtPos(loc, e)) => {
+                assert!(loc.file.ends_with("b.txt"));
+                // the line gets read back from the file, since files aren't kept in memory
+                assert_eq!(loc.line, "{{ include : a.txt }}");
+                match *e {
+                    RTError::IncludeCycle(files) => {
+                        assert_eq!(files.len(), 3);
+                        assert!(files[0].ends_with("a.txt") && files[2].ends_with("a.txt"));
+                    }
+                    e => panic!("expected an include cycle, not {}", e),
+                }
+            }
+            other => panic!("expected an include cycle, not {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn jump_into_another_file() {
+        let path = scratch_stories(
+            "story_jump_to_file",
+            &[
+                ("main.txt", "Start\n{{ jump_to_file : ch2.txt |,| cave }}\n"),
+                ("ch2.txt", "Outside\n#=$ cave $=#\nInside\n"),
+            ],
+        );
+        let story = Story::from_file(&path, 4).unwrap();
+        let mut next = story.load_neighbor("ch2.txt").unwrap();
+        assert!(!next.start_at_section("nowhere"));
+        assert!(next.start_at_section("cave"));
+        assert_eq!(next.curr_sect().get_name(), "cave");
+        assert_eq!(next.advance(DisplayUnit::Word), Span::Section);
+        assert!(matches!(next.get_curr(), Unit::Word(w) if w == "Inside"));
+
+        // The first section works too, even though a fresh story is already on it
+        let mut next = story.load_neighbor("ch2.txt").unwrap();
+        assert!(next.start_at_section("0"));
+        assert_eq!(next.advance(DisplayUnit::Word), Span::Section);
+        assert!(matches!(next.get_curr(), Unit::Word(w) if w == "Outside"));
+    }
+
+    #[test]
+    fn jump_within_a_story() {
+        let mut story: Story = BRANCHING.parse().unwrap();
+        story.advance(DisplayUnit::Word);
+        // Jumping to the section the story is already in starts it over, but isn't a jump
+        assert!(!story.jump_to_section(Some(&"Main Section".to_owned())));
+        assert_eq!(story.get_place(), Bookmark::default());
+        assert!(!story.jump_to_section(Some(&"nowhere".to_owned())));
+        assert!(story.jump_to_section(Some(&"middle".to_owned())));
+        assert_eq!(story.curr_sect().get_name(), "middle");
+        assert_eq!(story.advance(DisplayUnit::Word), Span::Section);
+    }
 }
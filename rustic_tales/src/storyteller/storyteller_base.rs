@@ -1,12 +1,18 @@
 use std::collections::HashMap;
-use std::fs;
 use std::path::Path;
 
-use crate::err::Result;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::buffer::Checkpoint;
+use crate::clock::Clock;
+use crate::err::{RTError, Result};
 use crate::options::{STOptions, ScrollRate};
+use crate::save::SaveFile;
 use crate::utils::*;
 
-use super::story::{Span, Story};
+use super::meta::StoryMeta;
+use super::story::{SavedPlace, Span, Story};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SnippetInfo {
@@ -39,12 +45,25 @@ impl SnippetInfo {
     }
 }
 
+// Somewhere the reader made a choice, and everything needed to go back and choose again
+#[derive(Debug, Clone)]
+pub struct Decision {
+    pub(super) file: String, // the story may have gone on to another file since (see jump_to_file)
+    pub(super) hash: u64,    // of the story in file, in case it changes before it's reloaded
+    pub(super) place: SavedPlace,
+    pub(super) env: HashMap<String, String>,
+    pub(super) page: Checkpoint,
+    pub(super) label: String, // what the reader sees when picking which decision to go back to
+}
+
 #[derive(Debug, Clone)]
 pub struct StoryTeller<'a, S> {
     pub(super) story: Story,
     pub(super) options: Option<&'a STOptions>,
     pub(super) term_settings: Option<termios::Termios>,
     pub(super) env: HashMap<String, String>,
+    pub(super) rng: StdRng,
+    pub(super) decisions: Vec<Decision>, // oldest first
     pub(super) state: S,
 }
 
@@ -92,17 +111,45 @@ impl<'a, S> StoryTeller<'a, S> {
     pub fn setup(&mut self, opts: &'a STOptions, term_settings: Option<termios::Termios>) {
         self.options = Some(opts);
         self.term_settings = term_settings;
+        if let Some(seed) = opts.seed {
+            self.rng = StdRng::seed_from_u64(seed);
+        }
+        self.update_clock();
+    }
+    // Time passes while a story is told, so the variables saying what time it is have to keep up
+    pub(super) fn update_clock(&mut self) {
+        for (var, val) in self.clock().builtins() {
+            self.env.insert(var.to_owned(), val);
+        }
+    }
+
+    pub fn get_meta(&self) -> &StoryMeta {
+        self.story.get_meta()
+    }
+    // Everything needed to pick the story back up later
+    pub fn save(&self) -> SaveFile {
+        SaveFile {
+            story: self.story.file_name(0).clone(),
+            hash: self.story.hash(),
+            place: self.story.saved_place(),
+            env: self.env.clone(),
+            saved_at: Clock::System.now(),
+        }
     }
 
     pub(super) fn opts(&self) -> &STOptions {
         self.options
             .expect("opts should only be called after setup")
     }
+    pub(super) fn clock(&self) -> Clock {
+        self.options.map_or(Clock::System, |opts| opts.clock)
+    }
     pub(super) fn get_full_path(&self, p: &str) -> String {
         format!("{}/{}", self.opts().stories_directory, p)
     }
-    pub(super) fn get_val(&self, var: &str) -> String {
-        self.env.get(var).unwrap_or(&String::new()).clone()
+    // Everything that reads a variable goes through here (see vars::var_value)
+    pub(super) fn lookup(&self, var: &str) -> Option<String> {
+        self.env.get(var).cloned()
     }
     pub(super) fn set_val(&mut self, var: String, val: String) {
         self.env.insert(var, val);
@@ -110,15 +157,79 @@ impl<'a, S> StoryTeller<'a, S> {
 }
 
 impl<'a, S: Default> StoryTeller<'a, S> {
-    pub fn new<P: AsRef<Path>>(story: P) -> Result<Self> {
-        let story: Story = fs::read_to_string(story)?.parse()?;
+    // tab_width is from BufOptions (see Story::from_file)
+    pub fn new<P: AsRef<Path>>(story: P, tab_width: usize) -> Result<Self> {
+        let story = Story::from_file(story, tab_width)?;
 
-        Ok(StoryTeller {
+        let mut st = StoryTeller {
             story,
             options: None,
             term_settings: None,
             env: StoryTeller::<S>::prepare_builtins(),
+            rng: StdRng::from_entropy(),
+            decisions: Vec::new(),
             state: Default::default(),
-        })
+        };
+        st.update_clock();
+        Ok(st)
+    }
+    // Picks a story back up where it was saved
+    pub fn from_save(save: &SaveFile, tab_width: usize) -> Result<Self> {
+        let mut st = StoryTeller::new(&save.story, tab_width)?;
+        if st.story.hash() != save.hash {
+            return Err(RTError::StoryChanged(save.story.clone()));
+        }
+        st.story.restore(&save.place)?;
+        st.env.extend(save.env.clone());
+        Ok(st)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    use crate::options::DisplayUnit;
+    use crate::storyteller::Telling;
+    use crate::utils::scratch_stories;
+
+    const BRANCHING: &str = "Once upon a time\n\n{{ goto : middle }}\n\nthe end\n\
+                             #=$ middle $=#\nSomewhere in the middle\n\n{{ return :}}\n";
+
+    #[test]
+    fn resume_from_save() {
+        let path = scratch_stories("resume", &[("story.txt", BRANCHING)]);
+        let mut st = StoryTeller::<Telling>::new(&path, 4).unwrap();
+        st.story.goto(&"middle".to_owned());
+        st.story.advance(DisplayUnit::Word);
+        st.story.advance(DisplayUnit::Word);
+        st.set_val("NAME".to_owned(), "Ada".to_owned());
+
+        // through a file, the way it happens for real
+        let file = format!("{}.json", path);
+        st.save().to_file(&file).unwrap();
+        let save = SaveFile::from_file(&file).unwrap();
+        let mut resumed = StoryTeller::<Telling>::from_save(&save, 4).unwrap();
+        assert_eq!(resumed.story.curr_sect().get_name(), "middle");
+        assert_eq!(resumed.story.get_place(), st.story.get_place());
+        assert_eq!(resumed.lookup("NAME").as_deref(), Some("Ada"));
+        // the goto still has somewhere to return to
+        assert!(resumed.story.return_to_caller());
+        assert_eq!(resumed.story.curr_sect().get_name(), "Main Section");
+    }
+
+    #[test]
+    fn story_changed_since_save() {
+        let path = scratch_stories("changed", &[("story.txt", BRANCHING)]);
+        let save = StoryTeller::<Telling>::new(&path, 4).unwrap().save();
+        assert!(StoryTeller::<Telling>::from_save(&save, 4).is_ok());
+
+        fs::write(&path, BRANCHING.replace("time", "midnight")).unwrap();
+        match StoryTeller::<Telling>::from_save(&save, 4) {
+            Err(RTError::StoryChanged(story)) => assert_eq!(story, path),
+            other => panic!("expected StoryChanged, not {:?}", other.map(|_| ())),
+        }
     }
 }
@@ -1,48 +1,52 @@
-use humantime::parse_duration;
-
 use std::fs;
-use std::io::{stdout, Write};
+use std::io::{stdin, stdout, Write};
 use std::num::NonZeroUsize;
-use std::thread::sleep;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, sleep};
 use std::time::{Duration, Instant};
 
-use script::token::{tokenize, Token};
+use script::token::{tokenize_spanned, Pos, Token};
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::buffer::TermBuffer;
 use crate::commands::prompts::*;
+use crate::commands::story_command::*;
+use crate::commands::vars::var_value;
 use crate::commands::*;
 use crate::err::{RTError, Result};
 use crate::options::{DisplayUnit, ScrollRate};
+use crate::save::{save_to_slot, SaveFile};
 use crate::utils::*;
 
-use super::story::{Span, Story};
+use super::story::{Origin, Span, Story};
 use super::storyteller_base::*;
 use super::unit::Unit;
 
 // All the states StoryTeller can be in
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Debug;
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Telling {
-    to: TransitionInfo,
+    to: Transition,
 }
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Paused {
-    from: TransitionInfo,
+    from: Transition,
+    to_look_back: bool, // paused just to look back at earlier pages (instead of by pressing p)
 }
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Quit;
 #[derive(Debug, Clone, Copy)]
 pub struct Backspacing {
-    unit: DisplayUnit,
-    num: usize,
-    pace: Duration,
+    pub(super) unit: DisplayUnit,
+    pub(super) num: usize,
+    pub(super) pace: Duration,
 }
 #[derive(Debug, Clone)]
 pub struct Repeating {
-    text: String,
-    num: usize,
-    pace: Duration,
+    pub(super) text: String,
+    pub(super) num: usize,
+    pub(super) pace: Duration,
 }
 #[derive(Debug, Clone, Copy)]
 pub struct WaitingForKB(Option<char>);
@@ -51,25 +55,167 @@ pub struct Sleeping {
     start: Instant,
     dur: Duration,
 }
-
 #[derive(Debug, Clone)]
-enum TransitionInfo {
+pub struct ForcingInput {
+    text: String, // what the reader has to type
+    typed: String,
+    last_erase: Instant,
+    erase_after: Duration,
+}
+#[derive(Debug, Clone)]
+pub struct PromptingYesNo {
+    var: String,
+    default: Option<String>,
+    input: YesNoInput,
+}
+#[derive(Debug, Clone)]
+pub struct ChoosingFromMenu {
+    var: String,
+    choices: Vec<String>,
+    input: MenuInput,
+}
+#[derive(Debug, Clone)]
+pub struct FetchingWord {
+    word: Arc<Mutex<Option<Result<Option<String>>>>>, // filled in by another thread
+    origin: Origin, // where the command was, in case fetching goes wrong
+    written: bool,
+}
+
+// What the story does next, once a command has started something that takes a while
+#[derive(Debug, Clone, Default)]
+pub enum Transition {
     Backspacing(Backspacing),
     Repeating(Repeating),
     WaitingForKB(WaitingForKB),
     Sleeping(Sleeping),
+    ForcingInput(ForcingInput),
+    PromptingYesNo(PromptingYesNo),
+    ChoosingFromMenu(ChoosingFromMenu),
+    FetchingWord(FetchingWord),
+    #[default]
     Nothing,
 }
 
-impl Default for TransitionInfo {
-    fn default() -> Self {
-        TransitionInfo::Nothing
+impl Transition {
+    fn is_nothing(&self) -> bool {
+        matches!(self, Transition::Nothing)
+    }
+    fn is_decision(&self) -> bool {
+        matches!(
+            self,
+            Transition::PromptingYesNo(..) | Transition::ChoosingFromMenu(..)
+        )
     }
 }
 
-impl TransitionInfo {
-    fn is_nothing(&self) -> bool {
-        matches!(self, TransitionInfo::Nothing)
+impl Transition {
+    pub fn sleep(dur: Duration) -> Transition {
+        Transition::Sleeping(Sleeping::starting_now(dur))
+    }
+}
+
+// What a state is in the middle of, so it can be picked back up after e.g. a pause
+impl From<Telling> for Transition {
+    fn from(st: Telling) -> Transition {
+        st.to
+    }
+}
+impl From<Paused> for Transition {
+    fn from(st: Paused) -> Transition {
+        st.from
+    }
+}
+impl From<Quit> for Transition {
+    fn from(_: Quit) -> Transition {
+        Transition::Nothing
+    }
+}
+macro_rules! transition_from {
+    ($($state:ident),*) => {
+        $(impl From<$state> for Transition {
+            fn from(st: $state) -> Transition {
+                Transition::$state(st)
+            }
+        })*
+    };
+}
+transition_from!(
+    Backspacing,
+    Repeating,
+    WaitingForKB,
+    Sleeping,
+    ForcingInput,
+    PromptingYesNo,
+    ChoosingFromMenu,
+    FetchingWord
+);
+
+impl Sleeping {
+    fn starting_now(dur: Duration) -> Sleeping {
+        Sleeping {
+            start: Instant::now(),
+            dur,
+        }
+    }
+}
+
+impl ForcingInput {
+    pub(super) fn new(text: String) -> ForcingInput {
+        ForcingInput {
+            text,
+            typed: String::new(),
+            last_erase: Instant::now(),
+            erase_after: SLOW_ERASE_THRESHOLD,
+        }
+    }
+    fn is_done(&self) -> bool {
+        self.typed == self.text
+    }
+    // Whether key is the next thing the reader has to type
+    fn expects(&self, key: u8) -> bool {
+        self.text.starts_with(&self.typed) && self.text[self.typed.len()..].starts_with(key as char)
+    }
+}
+
+impl PromptingYesNo {
+    pub(super) fn new(var: String, default: Option<String>) -> PromptingYesNo {
+        PromptingYesNo {
+            var,
+            default,
+            input: YesNoInput::default(),
+        }
+    }
+}
+
+impl ChoosingFromMenu {
+    pub(super) fn new(var: String, choices: Vec<String>) -> ChoosingFromMenu {
+        ChoosingFromMenu {
+            var,
+            choices,
+            input: MenuInput::default(),
+        }
+    }
+}
+
+impl FetchingWord {
+    pub(super) fn start(kind: String, origin: Origin) -> FetchingWord {
+        let word = Arc::new(Mutex::new(None));
+        let fetched = Arc::clone(&word);
+        thread::spawn(move || {
+            let phrase = random_phrase(&kind);
+            if let Ok(mut fetched) = fetched.lock() {
+                *fetched = Some(phrase);
+            }
+        });
+        FetchingWord {
+            word,
+            origin,
+            written: false,
+        }
+    }
+    // None until the other thread hears back (and after it's been taken once)
+    fn take(&self) -> Option<Result<Option<String>>> {
+        self.word.lock().ok().and_then(|mut word| word.take())
     }
 }
 
@@ -81,6 +227,121 @@ impl<'a, S> StoryTeller<'a, S> {
     fn quit(self) -> StoryTeller<'a, Quit> {
         self.into_state_def()
     }
+    // Back to telling once a command's done. If the story stopped right before a blocking
+    // command, that command still has to wait for a key press first.
+    fn finish_command(self, buf: &mut TermBuffer) -> StatefulStoryTeller<'a> {
+        let mut st = self.into_telling();
+        if !st.story.is_over() && st.story.get_curr().is_blocking_command() {
+            st.state.to = st.wait_kb();
+        }
+        st.transition(buf)
+    }
+    // Asks the reader what to call the save, so they can keep more than one
+    fn save_to_slot(&self, save: &SaveFile, buf: &mut TermBuffer) {
+        // The reader should be able to see what they're typing
+        let no_echo = change_term(self.term_settings);
+        print!("\nSave as (leave blank to not save): ");
+        let _ = stdout().flush();
+        let mut slot = String::new();
+        let _ = stdin().read_line(&mut slot);
+        change_term(no_echo);
+
+        let slot = slot.trim();
+        if !slot.is_empty() {
+            if let Err(e) = save_to_slot(&self.opts().saves_directory, slot, save) {
+                wait_for_kb_with_prompt(&format!("Could not save because '{}'", e));
+            }
+        }
+        buf.clear_and_dump();
+    }
+    // Lets the reader pick an earlier decision to go back to, and puts everything back how it
+    // was right before it was made. Returns whether the story went back.
+    fn rewind(&mut self, buf: &mut TermBuffer) -> bool {
+        if self.decisions.is_empty() {
+            return false;
+        }
+        let no_echo = change_term(self.term_settings);
+        clear_screen();
+        println!("Go back to which decision?");
+        let mut labels: Vec<_> = self.decisions.iter().map(|d| d.label.as_str()).collect();
+        labels.push("Never mind");
+        let choice = menu(&labels, None, false)
+            .ok()
+            .filter(|&idx| idx < self.decisions.len());
+        change_term(no_echo);
+
+        let went_back = choice.map(|idx| self.go_back_to(idx, buf));
+        if let Some(Err(e)) = &went_back {
+            wait_for_kb_with_prompt(&format!("Could not go back because '{}'", e));
+        }
+        buf.clear_and_dump();
+        matches!(went_back, Some(Ok(())))
+    }
+    // Puts everything back how it was right before decisions[idx] was made
+    fn go_back_to(&mut self, idx: usize, buf: &mut TermBuffer) -> Result<()> {
+        let decision = self.decisions[idx].clone();
+        self.restore_decision(&decision)?;
+        // It gets made again, and remembered again then
+        self.decisions.truncate(idx);
+        buf.rewind_to(decision.page);
+        Ok(())
+    }
+    // Puts the story back how it was right before the decision being made now (leaving the buffer
+    // alone), so quitting or saving in the middle of it asks again when the story's picked back up
+    fn undo_decision(mut self) -> Self {
+        if let Some(decision) = self.decisions.pop() {
+            // It's still on the command asking, so it's still in the same story and this can't fail
+            let _ = self.restore_decision(&decision);
+        }
+        self
+    }
+    // Reloads the story first if it's gone on to another file since decision was made (like
+    // picking up a save would)
+    fn restore_decision(&mut self, decision: &Decision) -> Result<()> {
+        if &decision.file == self.story.file_name(0) {
+            self.story.restore(&decision.place)?;
+        } else {
+            let mut story = Story::from_file(&decision.file, self.story.tab_width())?;
+            if story.hash() != decision.hash {
+                return Err(RTError::StoryChanged(decision.file.clone()));
+            }
+            story.restore(&decision.place)?;
+            self.story = story;
+        }
+        self.env = decision.env.clone();
+        Ok(())
+    }
+    // Whatever the story was in the middle of, it'll pick back up once it's unpaused
+    fn pause(self) -> StoryTeller<'a, Paused>
+    where
+        S: Clone + Into<Transition>,
+    {
+        let from = self.state.clone().into();
+        self.into_state(Paused {
+            from,
+            to_look_back: false,
+        })
+    }
+    // Starts doing whatever to is
+    fn start(self, to: Transition) -> StatefulStoryTeller<'a> {
+        match to {
+            Transition::Backspacing(bs) => StatefulStoryTeller::Backspacing(self.into_state(bs)),
+            Transition::Repeating(rp) => StatefulStoryTeller::Repeating(self.into_state(rp)),
+            Transition::WaitingForKB(wfkb) => {
+                StatefulStoryTeller::WaitingForKB(self.into_state(wfkb))
+            }
+            Transition::Sleeping(sleep) => StatefulStoryTeller::Sleeping(self.into_state(sleep)),
+            Transition::ForcingInput(fi) => StatefulStoryTeller::ForcingInput(self.into_state(fi)),
+            Transition::PromptingYesNo(yn) => {
+                StatefulStoryTeller::PromptingYesNo(self.into_state(yn))
+            }
+            Transition::ChoosingFromMenu(menu) => {
+                StatefulStoryTeller::ChoosingFromMenu(self.into_state(menu))
+            }
+            Transition::FetchingWord(fw) => StatefulStoryTeller::FetchingWord(self.into_state(fw)),
+            Transition::Nothing => StatefulStoryTeller::Telling(self.into_telling()),
+        }
+    }
     fn into_state_def<SS: Default>(self) -> StoryTeller<'a, SS> {
         self.into_state(SS::default())
     }
@@ -90,6 +351,8 @@ impl<'a, S> StoryTeller<'a, S> {
             options: self.options,
             term_settings: self.term_settings,
             env: self.env,
+            rng: self.rng,
+            decisions: self.decisions,
             state,
         }
     }
@@ -97,14 +360,16 @@ impl<'a, S> StoryTeller<'a, S> {
 
 impl<'a> StoryTeller<'a, Telling> {
     fn write(&mut self, buf: &mut TermBuffer) {
-        // self.eval_command mutably borrows self, so need to clone or something
+        self.update_clock();
+        // running a command mutably borrows self, so need to clone or something
         let unit = self.story.get_curr().clone();
         match unit {
             Unit::Char(c) => buf.write_char(c),
             Unit::Word(w) => {
-                if self.opts().disp_by == DisplayUnit::Char {
-                    buf.write_char(
-                        w.chars()
+                if self.opts().disp_by.is_char() {
+                    // a 'letter' is a whole grapheme, so accents don't show up a beat late
+                    buf.write_text(
+                        w.graphemes(true)
                             .nth(self.story.get_place().letter)
                             .expect("story.place should be a valid index"),
                     );
@@ -116,17 +381,22 @@ impl<'a> StoryTeller<'a, Telling> {
             Unit::Special(t) => {
                 debug_assert!(!t.is_text() && !t.is_page_end() && !t.is_sect_start());
                 match t {
-                    Token::Variable(s) => buf.write_text(&self.get_val(&s)),
-                    Token::Symbol(s) => buf.write_text(&format!("${}$", s)),
-                    Token::Command(func, args, _) => {
-                        // Do I want this?
-                        if let Err(e) = self.eval_command(&func, &args, buf) {
-                            eprintln!("\nError: {}", e)
-                        }
+                    Token::Variable(_) | Token::VariableOr(..) => {
+                        buf.write_text(&var_value(&t, |var| self.lookup(var)))
                     }
+                    Token::Symbol(s) => buf.write_text(&format!("${}$", s)),
                     _ => unreachable!(),
                 }
             }
+            Unit::Command(call, _) => {
+                // the command might jump somewhere else, so remember where we were
+                let origin = self.story.curr_origin();
+                // Do I want this?
+                match call.run(self, buf) {
+                    Ok(to) => self.state.to = to,
+                    Err(e) => eprintln!("\nError: {}", self.story.locate(origin, e)),
+                }
+            }
         }
     }
     fn write_and_advance(&mut self, buf: &mut TermBuffer, disp_by: DisplayUnit) -> Option<Span> {
@@ -136,13 +406,13 @@ impl<'a> StoryTeller<'a, Telling> {
         if ret == Span::Page && !self.story.is_over() {
             //self.turn_page(buf);
         }
-        the_story_goes_on.then(|| ret)
+        the_story_goes_on.then_some(ret)
     }
     fn tell_millis(&mut self, buf: &mut TermBuffer, num: NonZeroUsize, ms: u64) -> SnippetInfo {
         let mut info = SnippetInfo::Nothing;
         for _ in 0..num.get() {
             let span = self.write_and_advance(buf, self.opts().disp_by);
-            if span == None {
+            if span.is_none() {
                 info = SnippetInfo::StoryOver;
                 break;
             } else if self.story.get_curr().is_blocking_command() {
@@ -235,269 +505,63 @@ impl<'a> StoryTeller<'a, Telling> {
         info
     }
 
-    fn parse_arg(&self, arg: &str) -> Result<String> {
-        use Token::*;
-        let mut tkns = tokenize(arg);
-        if tkns.len() != 1 {
-            let msg = format!("Could not parse arg '{}'", arg);
-            let e = RTError::InvalidInput(msg);
-            return Err(e);
-        }
-        let tkn = tkns.pop().unwrap();
-        match tkn {
-            Text(s) => Ok(s),
-            Symbol(s) => Ok(s),
-            Variable(v) => Ok(self.get_val(&v)),
-            _ => {
-                let msg = format!("commands can only take text, symbols, and variables. {} is none of they above.", arg);
-                let e = RTError::InvalidInput(msg);
-                Err(e)
-            }
-        }
-    }
-    fn eval_command(&mut self, func: &str, args: &[String], buf: &mut TermBuffer) -> Result<()> {
-        match func {
-            "backspace" => {
-                if args.len() < 2 {
-                    Err(RTError::InvalidInput(
-                        "'backspace' requires two arguments".to_string(),
-                    ))
-                } else if args.len() == 2 || args[2] != "one_by_one" {
-                    //                    ^^^^^^^^^^^^^^^^^^^^^^^^^^
-                    // Really hope Rust does short circuiting
-                    backspace(args[0].parse()?, args[1].parse()?, buf);
-                    Ok(())
-                } else {
-                    let pace = if args.len() >= 4 {
-                        parse_duration(&args[3])?
-                    } else {
-                        Duration::from_millis(250)
-                    };
-                    self.state.to = TransitionInfo::Backspacing(Backspacing {
-                        unit: args[1].parse()?,
-                        num: args[0].parse()?,
-                        pace,
-                    });
-                    Ok(())
-                }
-            }
-            "display_img" => {
-                if !matches!(args.len(), 1 | 2) {
-                    Err(RTError::InvalidInput(
-                        "'display_img' takes 1 or 2 args".to_string(),
-                    ))
-                } else if args.len() == 2 && args[1].eq_ignore_ascii_case("term") {
-                    let _ = img_to_term(self.get_full_path(&args[0]), buf)?;
-                    Ok(())
-                } else {
-                    let _ = img_to_ascii(self.get_full_path(&args[0]), buf)?;
-                    Ok(())
-                }
-            }
-            "prompt_yesno" => {
-                if args.is_empty() {
-                    let e = RTError::InvalidInput("'prompt_yesno' requires an argument".to_owned());
-                    Err(e)
-                } else {
-                    // This should probably check that args[0] is a Token::Symbol, but what kinda
-                    // person has the patience to write correct code?
-                    self.set_val(
-                        self.parse_arg(&args[0])?,
-                        prompt_yesno(args.get(1).cloned(), self.term_settings, buf),
-                    );
-                    Ok(())
-                }
-            }
-            "jump_if_eq" => {
-                if args.len() < 3 {
-                    let msg = "'jump_if_eq' requires at least 3 arguments".to_owned();
-                    let e = RTError::InvalidInput(msg);
-                    Err(e)
-                } else {
-                    let lhs = self.parse_arg(&args[0])?;
-                    let rhs = self.parse_arg(&args[1])?;
-                    let jump_happened = if lhs == rhs {
-                        self.story.jump_to_section(args.get(2))
-                    } else {
-                        self.story.jump_to_section(args.get(3))
-                    };
-                    if jump_happened { /* TODO */ }
-                    Ok(())
-                }
-            }
-            "pause" => {
-                if args.len() != 1 {
-                    let msg = "'pause' takes exactly 1 argument".to_owned();
-                    let e = RTError::InvalidInput(msg);
-                    Err(e)
-                } else {
-                    let dur = parse_duration(&args[0])?;
-                    self.state.to = TransitionInfo::Sleeping(Sleeping {
-                        dur,
-                        start: Instant::now(),
-                    });
-                    /*
-                    let _ = std::io::stdout().flush();
-                    let orig = no_term_echo();
-                    sleep(dur);
-                    restore_term(orig);
-                    // Ignore all keys user pressed while paused
-                    exhaust_kb();
-                    */
-                    Ok(())
-                }
-            }
-            "force_input" => {
-                if args.len() != 1 {
-                    let msg = "'force_input' takes exactly 1 argument".to_owned();
-                    let e = RTError::InvalidInput(msg);
-                    Err(e)
-                } else {
-                    force_input(&self.parse_arg(&args[0])?, self.term_settings, buf)
-                }
-            }
-            "choice_menu" => {
-                if args.len() < 2 {
-                    let msg = "'choice_menu' requires at least 1 choice".to_owned();
-                    let e = RTError::InvalidInput(msg);
-                    Err(e)
-                } else {
-                    // This should probably check that args[0] is a Token::Symbol, but what kinda
-                    // person has the patience to write correct code?
-                    self.set_val(
-                        self.parse_arg(&args[0])?,
-                        choice_menu(&args[1..], self.term_settings, buf)?,
-                    );
-                    Ok(())
-                }
-            }
-            "wait_kb" => Ok(self.wait_kb()),
-            "move_cursor_back" => {
-                if args.len() != 1 {
-                    Err(RTError::WrongNumArguments(
-                        "move_cursor_back",
-                        "1",
-                        args.len(),
-                    ))
-                } else {
-                    buf.move_cursor(-args[0].parse()?);
-                    Ok(())
-                }
-            }
-            "clear_screen" => {
-                clear_screen();
-                Ok(())
-            }
-            "repeat" => {
-                if args.len() != 3 {
-                    Err(RTError::WrongNumArguments("repeat", "3", args.len()))
-                } else {
-                    self.state.to = TransitionInfo::Repeating(Repeating {
-                        text: self.parse_arg(&args[0])?,
-                        num: args[1].parse()?,
-                        pace: parse_duration(&args[2])?,
-                    });
-                    Ok(())
-                }
-            }
-            "random_word_generator" => {
-                if args.len() != 1 {
-                    Err(RTError::WrongNumArguments(
-                        "random_word_generator",
-                        "1",
-                        args.len(),
-                    ))
-                } else {
-                    let res =
-                        get_random_phrase(&self.parse_arg(&args[0])?.to_ascii_lowercase(), buf);
-                    sleep(Duration::from_millis(1000));
-                    res
-                }
-            }
-
-            _ => Err(RTError::UnrecognizedCommand(func.to_string())),
-        }
+    // The value of arg now, given what the story's variables are
+    pub fn eval<T: ArgType>(&self, arg: &Arg<T>) -> Result<T> {
+        arg.eval(|var| self.lookup(var))
     }
-    fn wait_kb(&mut self) {
+    // Called right before the reader is asked something, while the story is still on the command
+    // doing the asking, so going back here asks again
+    pub(super) fn remember_decision(&mut self, buf: &TermBuffer) {
+        let line = buf.curr_line();
+        let label = format!(
+            "\"{}\" (in section '{}')",
+            line.trim(),
+            self.story.curr_sect().get_name()
+        );
+        self.decisions.push(Decision {
+            file: self.story.file_name(0).clone(),
+            hash: self.story.hash(),
+            place: self.story.saved_place(),
+            env: self.env.clone(),
+            page: buf.checkpoint(),
+            label,
+        });
+    }
+    pub fn wait_kb(&self) -> Transition {
         // Just in case there are some left over keys that haven't been exhausted yet. We'd hate
-        // for this function to return before the user actually performs a new key press.
+        // for the wait to end before the user actually performs a new key press.
         exhaust_kb();
-        self.state.to = TransitionInfo::WaitingForKB(WaitingForKB(self.opts().prompt_when_wait));
+        Transition::WaitingForKB(WaitingForKB(self.opts().prompt_when_wait))
     }
 
-    fn pause(self) -> StoryTeller<'a, Paused> {
-        StoryTeller {
-            story: self.story,
-            options: self.options,
-            term_settings: self.term_settings,
-            env: self.env,
-            state: Paused::default(),
-        }
-    }
     fn transition(self, buf: &mut TermBuffer) -> StatefulStoryTeller<'a> {
-        match self.state.to.clone() {
-            TransitionInfo::Backspacing(bs) => {
-                StatefulStoryTeller::Backspacing(self.into_state(bs))
-            }
-            TransitionInfo::Repeating(rp) => StatefulStoryTeller::Repeating(self.into_state(rp)),
-            TransitionInfo::WaitingForKB(wfkb) => {
-                if let Some(ref c) = wfkb.0 {
-                    buf.write_char(*c);
-                    let _ = stdout().flush();
-                }
-                StatefulStoryTeller::WaitingForKB(self.into_state(wfkb))
-            }
-            TransitionInfo::Sleeping(sleep) => {
-                StatefulStoryTeller::Sleeping(self.into_state(sleep))
-            }
-            TransitionInfo::Nothing => StatefulStoryTeller::Telling(self),
+        let to = self.state.to.clone();
+        if let Transition::WaitingForKB(WaitingForKB(Some(c))) = to {
+            buf.write_char(c);
+            let _ = stdout().flush();
         }
+        self.start(to)
     }
 }
 
 impl<'a> StoryTeller<'a, Paused> {
-    fn resume(self) -> StatefulStoryTeller<'a> {
-        match self.state.from.clone() {
-            TransitionInfo::Backspacing(bs) => {
-                StatefulStoryTeller::Backspacing(self.into_state(bs))
-            }
-            TransitionInfo::Repeating(rp) => StatefulStoryTeller::Repeating(self.into_state(rp)),
-            TransitionInfo::WaitingForKB(wfkb) => {
-                StatefulStoryTeller::WaitingForKB(self.into_state(wfkb))
-            }
-            TransitionInfo::Sleeping(sleep) => {
-                StatefulStoryTeller::Sleeping(self.into_state(sleep))
-            }
-            TransitionInfo::Nothing => StatefulStoryTeller::Telling(self.into_telling()),
-        }
+    // The story stays paused until the reader comes back to the current page
+    fn look_back(mut self, buf: &mut TermBuffer) -> StatefulStoryTeller<'a> {
+        buf.look_back();
+        self.state.to_look_back = true;
+        StatefulStoryTeller::Paused(self)
     }
-}
-
-impl<'a> StoryTeller<'a, Backspacing> {
-    fn pause(self) -> StoryTeller<'a, Paused> {
-        StoryTeller {
-            story: self.story,
-            options: self.options,
-            term_settings: self.term_settings,
-            env: self.env,
-            state: Paused {
-                from: TransitionInfo::Backspacing(self.state),
-            },
+    fn look_forward(self, buf: &mut TermBuffer) -> StatefulStoryTeller<'a> {
+        buf.look_forward();
+        if !buf.is_looking_back() && self.state.to_look_back {
+            self.resume()
+        } else {
+            StatefulStoryTeller::Paused(self)
         }
     }
-}
-
-impl<'a> StoryTeller<'a, Repeating> {
-    fn pause(self) -> StoryTeller<'a, Paused> {
-        StoryTeller {
-            story: self.story,
-            options: self.options,
-            term_settings: self.term_settings,
-            env: self.env,
-            state: Paused {
-                from: TransitionInfo::Repeating(self.state),
-            },
-        }
+    fn resume(self) -> StatefulStoryTeller<'a> {
+        let from = self.state.from.clone();
+        self.start(from)
     }
 }
 
@@ -516,37 +580,164 @@ impl<'a> StoryTeller<'a, WaitingForKB> {
 }
 
 impl<'a> StoryTeller<'a, Debug> {
-    pub fn get_tokens(story: &str) -> Result<Vec<Token>> {
-        Ok(tokenize(&fs::read_to_string(story)?))
+    pub fn get_tokens(story: &str) -> Result<Vec<(Token, Pos)>> {
+        Ok(tokenize_spanned(&fs::read_to_string(story)?))
     }
     pub fn get_story(&self) -> &Story {
         &self.story
     }
+    pub fn is_builtin(&self, var: &str) -> bool {
+        self.env.contains_key(var)
+    }
 }
 
-impl<'a> StoryTeller<'a, Sleeping> {
-    fn pause(self) -> StoryTeller<'a, Paused> {
-        StoryTeller {
-            story: self.story,
-            options: self.options,
-            term_settings: self.term_settings,
-            env: self.env,
-            state: Paused {
-                from: TransitionInfo::Sleeping(self.state),
-            },
+impl<'a> StoryTeller<'a, ForcingInput> {
+    fn type_key(&mut self, key: u8, buf: &mut TermBuffer) {
+        let c = key as char;
+        if is_forceable(c) {
+            self.state.erase_after = SLOW_ERASE_THRESHOLD;
+            self.state.typed.push(c);
+            buf.write_char(c);
         }
     }
+    // The finished input stays up a moment before the story goes on
+    fn linger(self) -> StoryTeller<'a, Sleeping> {
+        self.into_state(Sleeping::starting_now(FORCED_INPUT_LINGER))
+    }
+    // Anything typed that isn't what the reader was supposed to type doesn't stay for long
+    fn erase_wrong_input(&mut self, buf: &mut TermBuffer) {
+        let now = Instant::now();
+        let st = &mut self.state;
+        if st.text.starts_with(&st.typed) {
+            st.last_erase = now;
+        } else if now.duration_since(st.last_erase) > st.erase_after {
+            st.last_erase = now;
+            st.erase_after = FAST_ERASE_THRESHOLD;
+            buf.erase_chars(1);
+            st.typed.pop();
+        }
+    }
+}
+
+impl<'a> StoryTeller<'a, PromptingYesNo> {
+    fn key_pressed(mut self, key: u8, buf: &mut TermBuffer) -> StatefulStoryTeller<'a> {
+        let default = self.state.default.as_deref();
+        match self.state.input.key_pressed(key, default) {
+            Some(answer) => {
+                let var = self.state.var.clone();
+                self.set_val(var, answer);
+                self.finish_command(buf)
+            }
+            None => StatefulStoryTeller::PromptingYesNo(self),
+        }
+    }
+}
+
+impl<'a> StoryTeller<'a, ChoosingFromMenu> {
+    fn key_pressed(mut self, key: u8, buf: &mut TermBuffer) -> StatefulStoryTeller<'a> {
+        let num_choices = self.state.choices.len();
+        match self.state.input.key_pressed(key, num_choices) {
+            Some(idx) => {
+                let (var, choice) = (self.state.var.clone(), self.state.choices[idx].clone());
+                self.set_val(var, choice);
+                self.finish_command(buf)
+            }
+            None => StatefulStoryTeller::ChoosingFromMenu(self),
+        }
+    }
+}
+
+impl<'a> StoryTeller<'a, FetchingWord> {
+    // Gives the reader a second to take in whatever got written
+    fn linger(self) -> StoryTeller<'a, Sleeping> {
+        self.into_state(Sleeping::starting_now(Duration::from_secs(1)))
+    }
+    fn write_word(&mut self, buf: &mut TermBuffer) {
+        if self.state.written {
+            return;
+        }
+        if let Some(res) = self.state.take() {
+            match res {
+                Ok(Some(phrase)) => buf.write_text(&phrase),
+                Ok(None) => {}
+                Err(e) => eprintln!("\nError: {}", self.story.locate(self.state.origin, e)),
+            }
+            self.state.written = true;
+        }
+    }
+}
+
+// Runs body on the StoryTeller inside a StatefulStoryTeller, whatever state it's in. The second
+// name (if given) is the variant it came from, so it can be put back the same way.
+macro_rules! with_any_state {
+    ($this:expr, |$st:pat| $body:expr) => {
+        with_any_state!($this, |$st, _variant| $body)
+    };
+    ($this:expr, |$st:pat, $variant:ident| $body:expr) => {{
+        use StatefulStoryTeller as SST;
+        match $this {
+            SST::Telling($st) => {
+                let $variant = SST::Telling;
+                $body
+            }
+            SST::Paused($st) => {
+                let $variant = SST::Paused;
+                $body
+            }
+            SST::Quit($st) => {
+                let $variant = SST::Quit;
+                $body
+            }
+            SST::Backspacing($st) => {
+                let $variant = SST::Backspacing;
+                $body
+            }
+            SST::Repeating($st) => {
+                let $variant = SST::Repeating;
+                $body
+            }
+            SST::WaitingForKB($st) => {
+                let $variant = SST::WaitingForKB;
+                $body
+            }
+            SST::Sleeping($st) => {
+                let $variant = SST::Sleeping;
+                $body
+            }
+            SST::ForcingInput($st) => {
+                let $variant = SST::ForcingInput;
+                $body
+            }
+            SST::PromptingYesNo($st) => {
+                let $variant = SST::PromptingYesNo;
+                $body
+            }
+            SST::ChoosingFromMenu($st) => {
+                let $variant = SST::ChoosingFromMenu;
+                $body
+            }
+            SST::FetchingWord($st) => {
+                let $variant = SST::FetchingWord;
+                $body
+            }
+        }
+    }};
 }
 
 #[derive(Debug)]
 pub enum StatefulStoryTeller<'a> {
     Telling(StoryTeller<'a, Telling>),
     Paused(StoryTeller<'a, Paused>),
+    #[allow(dead_code)]
     Quit(StoryTeller<'a, Quit>),
     Backspacing(StoryTeller<'a, Backspacing>),
     Repeating(StoryTeller<'a, Repeating>),
     WaitingForKB(StoryTeller<'a, WaitingForKB>),
     Sleeping(StoryTeller<'a, Sleeping>),
+    ForcingInput(StoryTeller<'a, ForcingInput>),
+    PromptingYesNo(StoryTeller<'a, PromptingYesNo>),
+    ChoosingFromMenu(StoryTeller<'a, ChoosingFromMenu>),
+    FetchingWord(StoryTeller<'a, FetchingWord>),
 }
 
 impl<'a> StatefulStoryTeller<'a> {
@@ -563,8 +754,11 @@ impl<'a> StatefulStoryTeller<'a> {
                     ScrollRate::Lines(num) => st.tell_lines(buf, num),
                     ScrollRate::OnePage => st.tell_onepage(buf),
                 };
-                if snippet_info.should_wait_for_kb(&st.opts().scroll_rate) {
-                    st.wait_kb();
+                // a command that's still going gets to finish first (see finish_command)
+                if snippet_info.should_wait_for_kb(&st.opts().scroll_rate)
+                    && st.state.to.is_nothing()
+                {
+                    st.state.to = st.wait_kb();
                 }
                 snippet_info
             }
@@ -572,14 +766,10 @@ impl<'a> StatefulStoryTeller<'a> {
             Quit(..) => SnippetInfo::StoryOver,
             Backspacing(st) => {
                 if st.state.num > 0 {
-                    if st.state.unit.is_char() {
-                        buf.erase_chars(1);
-                        st.state.num -= 1;
-                        let _ = stdout().flush();
-                        sleep(st.state.pace);
-                    } else {
-                        unimplemented!()
-                    }
+                    backspace(1, st.state.unit, buf);
+                    st.state.num -= 1;
+                    let _ = stdout().flush();
+                    sleep(st.state.pace);
                 }
                 SnippetInfo::Nothing
             }
@@ -595,51 +785,144 @@ impl<'a> StatefulStoryTeller<'a> {
             }
             WaitingForKB(..) => SnippetInfo::Nothing,
             Sleeping(..) => SnippetInfo::Nothing,
+            ForcingInput(st) => {
+                st.erase_wrong_input(buf);
+                SnippetInfo::Nothing
+            }
+            PromptingYesNo(..) | ChoosingFromMenu(..) => SnippetInfo::Nothing,
+            FetchingWord(st) => {
+                st.write_word(buf);
+                SnippetInfo::Nothing
+            }
+        }
+    }
+    // Whether the key is an answer to something the story asked (so it shouldn't e.g. pause)
+    fn wants_key(&self, key: u8) -> bool {
+        use StatefulStoryTeller::*;
+        match self {
+            ForcingInput(st) => st.state.expects(key),
+            PromptingYesNo(st) => st.state.input.wants(key),
+            ChoosingFromMenu(..) => MenuInput::wants(key),
+            _ => false,
         }
     }
     pub fn transition(self, buf: &mut TermBuffer) -> Self {
+        self.press(get_kb(), buf)
+    }
+    // What happens next, given the key the reader pressed (if any)
+    fn press(self, key: Option<u8>, buf: &mut TermBuffer) -> Self {
         use StatefulStoryTeller::*;
 
         const ESC_KEY: u8 = 27;
-        match get_kb() {
+        match key {
+            Some(k) if self.wants_key(k) => match self {
+                ForcingInput(mut st) => {
+                    st.type_key(k, buf);
+                    ForcingInput(st)
+                }
+                PromptingYesNo(st) => st.key_pressed(k, buf),
+                ChoosingFromMenu(st) => st.key_pressed(k, buf),
+                _ => self,
+            },
             Some(b'p') => match self {
-                Telling(st) => Paused(st.pause()),
-                Paused(st) => st.resume(),
-                Backspacing(st) => Paused(st.pause()),
-                Repeating(st) => Paused(st.pause()),
+                Paused(st) => {
+                    buf.stop_looking_back();
+                    st.resume()
+                }
                 Quit(..) | WaitingForKB(..) => self,
-                Sleeping(st) => Paused(st.pause()),
+                _ => with_any_state!(self, |st| Paused(st.pause())),
             },
-            Some(b'q') => match self {
-                Telling(st) => Quit(st.quit()),
-                Paused(st) => Quit(st.quit()),
-                Backspacing(st) => Quit(st.quit()),
-                Repeating(st) => Quit(st.quit()),
+            Some(b'b') if buf.can_look_back() => match self {
+                // Already paused, so stay that way after coming back
+                Paused(st) => {
+                    buf.look_back();
+                    Paused(st)
+                }
+                // It's just another key when the story asked for one
+                WaitingForKB(st) => Telling(st.key_pressed(buf)),
+                Quit(..) => self,
+                _ => with_any_state!(self, |st| st.pause().look_back(buf)),
+            },
+            Some(b'f') if buf.is_looking_back() => match self {
+                Paused(st) => st.look_forward(buf),
+                _ => self,
+            },
+            Some(b'r') => match self {
                 WaitingForKB(st) => Telling(st.key_pressed(buf)),
-                Sleeping(st) => Quit(st.quit()),
                 Quit(..) => self,
+                _ => with_any_state!(self, |mut st, variant| {
+                    if st.rewind(buf) {
+                        Telling(st.into_telling())
+                    } else {
+                        variant(st)
+                    }
+                }),
             },
-            Some(ESC_KEY) => match self {
-                Telling(st) => Quit(st.quit()),
-                Paused(st) => Quit(st.quit()),
-                Backspacing(st) => Quit(st.quit()),
-                Repeating(st) => Quit(st.quit()),
-                WaitingForKB(st) => Quit(st.quit()),
-                Sleeping(st) => Quit(st.quit()),
+            Some(b's') => match self {
+                WaitingForKB(st) => Telling(st.key_pressed(buf)),
                 Quit(..) => self,
+                _ => {
+                    self.save_to_slot(buf);
+                    self
+                }
+            },
+            Some(b'q') => match self {
+                WaitingForKB(st) => Telling(st.key_pressed(buf)),
+                _ => self.quit(),
             },
+            Some(ESC_KEY) => self.quit(),
             k => match self {
-                Backspacing(st) if st.state.num == 0 => Telling(st.into_telling()),
-                Repeating(st) if st.state.num == 0 => Telling(st.into_telling()),
+                Backspacing(st) if st.state.num == 0 => st.finish_command(buf),
+                Repeating(st) if st.state.num == 0 => st.finish_command(buf),
                 WaitingForKB(st) if k.is_some() => Telling(st.key_pressed(buf)),
                 Sleeping(st) if Instant::now().duration_since(st.state.start) >= st.state.dur => {
-                    Telling(st.into_telling())
+                    st.finish_command(buf)
                 }
+                ForcingInput(st) if st.state.is_done() => Sleeping(st.linger()),
+                // Wrong keys get typed too (and then erased)
+                ForcingInput(mut st) if k.is_some() => {
+                    st.type_key(k.unwrap_or_default(), buf);
+                    ForcingInput(st)
+                }
+                FetchingWord(st) if st.state.written => Sleeping(st.linger()),
                 Telling(st) => st.transition(buf),
                 _ => self,
             },
         }
     }
+    // Whether the reader is in the middle of making a decision (even if it's paused)
+    fn in_decision(&self) -> bool {
+        use StatefulStoryTeller::*;
+        match self {
+            Paused(st) => st.state.from.is_decision(),
+            PromptingYesNo(..) | ChoosingFromMenu(..) => true,
+            _ => false,
+        }
+    }
+    // Quitting in the middle of a decision goes back to right before it (see undo_decision)
+    fn quit(self) -> Self {
+        use StatefulStoryTeller::*;
+        match self {
+            Quit(..) => self,
+            _ if self.in_decision() => with_any_state!(self, |st| Quit(st.undo_decision().quit())),
+            _ => with_any_state!(self, |st| Quit(st.quit())),
+        }
+    }
+    pub fn save(&self) -> SaveFile {
+        if self.in_decision() {
+            with_any_state!(self, |st| st.clone().undo_decision().save())
+        } else {
+            with_any_state!(self, |st| st.save())
+        }
+    }
+    fn save_to_slot(&self, buf: &mut TermBuffer) {
+        let save = self.save();
+        with_any_state!(self, |st| st.save_to_slot(&save, buf))
+    }
+    // As opposed to reaching the end
+    pub fn quit_early(&self) -> bool {
+        matches!(self, StatefulStoryTeller::Quit(..))
+    }
     pub fn state_str(&self) -> String {
         use StatefulStoryTeller::*;
         match self {
@@ -649,14 +932,338 @@ impl<'a> StatefulStoryTeller<'a> {
             Repeating(..) => "Repeating".to_owned(),
             WaitingForKB(..) => "Waiting for keybaord".to_owned(),
             Sleeping(st) => format!("Sleeping ({:?})", st.state.dur),
+            ForcingInput(..) => "Forcing input".to_owned(),
+            PromptingYesNo(..) => "Prompting (y/n)".to_owned(),
+            ChoosingFromMenu(..) => "Choosing".to_owned(),
+            FetchingWord(..) => "Fetching a random word".to_owned(),
             Quit(..) => "Quit".to_owned(),
         }
     }
     pub fn info_str(&self) -> Option<String> {
         use StatefulStoryTeller::*;
         match self {
+            // the buffer says when it's showing an earlier page
+            Paused(st) if st.state.to_look_back => None,
             Paused(..) => Some("Paused".to_owned()),
+            PromptingYesNo(st) => Some(st.state.input.show(st.state.default.as_deref())),
+            ChoosingFromMenu(st) => Some(st.state.input.show(&st.state.choices)),
             _ => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::options::{BufOptions, STOptions};
+    use crate::utils::scratch_stories;
+
+    // A storyteller that's ready to tell story (from a file called name)
+    fn teller<'a>(name: &str, story: &str, opts: &'a STOptions) -> StoryTeller<'a, Telling> {
+        let path = scratch_stories(name, &[("story.txt", story)]);
+        let mut st = StoryTeller::new(path, 4).unwrap();
+        st.setup(opts, None);
+        st
+    }
+
+    // Tells the story (without pressing anything) until it gets to state
+    fn tell_until<'a>(
+        mut narrator: StatefulStoryTeller<'a>,
+        buf: &mut TermBuffer,
+        state: &str,
+    ) -> StatefulStoryTeller<'a> {
+        for _ in 0..100 {
+            if narrator.state_str() == state {
+                return narrator;
+            }
+            narrator.step(buf);
+            narrator = narrator.press(None, buf);
+        }
+        panic!("never got to {}, got stuck {}", state, narrator.state_str());
+    }
+
+    fn press_keys<'a>(
+        mut narrator: StatefulStoryTeller<'a>,
+        buf: &mut TermBuffer,
+        keys: &str,
+    ) -> StatefulStoryTeller<'a> {
+        for key in keys.bytes() {
+            narrator = narrator.press(Some(key), buf);
+        }
+        narrator
+    }
+
+    fn one_page() -> STOptions {
+        STOptions {
+            scroll_rate: ScrollRate::OnePage,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn forcing_input() {
+        let (opts, buf_opts) = (one_page(), BufOptions::default());
+        let story = "{{ force_input : hi }}\nDone\n";
+        let narrator = StatefulStoryTeller::from_telling(teller("force", story, &opts));
+        let mut buf = TermBuffer::new(&buf_opts);
+        let narrator = tell_until(narrator, &mut buf, "Forcing input");
+
+        // p isn't part of what has to be typed, so it pauses (and then resumes)
+        let narrator = press_keys(narrator, &mut buf, "hp");
+        assert_eq!(narrator.state_str(), "Paused");
+        let narrator = press_keys(narrator, &mut buf, "p");
+        assert_eq!(narrator.state_str(), "Forcing input");
+        assert_eq!(buf.curr_line(), "h");
+
+        let narrator = press_keys(narrator, &mut buf, "i");
+        assert_eq!(buf.curr_line(), "hi");
+        let narrator = narrator.press(None, &mut buf);
+        let lingering = format!("Sleeping ({:?})", FORCED_INPUT_LINGER);
+        assert_eq!(narrator.state_str(), lingering);
+    }
+
+    #[test]
+    fn quitting_force_input() {
+        let (opts, buf_opts) = (one_page(), BufOptions::default());
+        let story = "{{ force_input : quit }}\nDone\n";
+        for (name, quit_key) in [("force_q", "q"), ("force_esc", "\x1b")] {
+            let narrator = StatefulStoryTeller::from_telling(teller(name, story, &opts));
+            let mut buf = TermBuffer::new(&buf_opts);
+            let narrator = tell_until(narrator, &mut buf, "Forcing input");
+            // q is the next thing to type here, so it gets typed instead of quitting
+            let narrator = press_keys(narrator, &mut buf, "q");
+            assert_eq!(narrator.state_str(), "Forcing input");
+            // but once it's been typed, q quits like any other time (and so does esc)
+            let narrator = press_keys(narrator, &mut buf, quit_key);
+            assert!(narrator.quit_early(), "{:?} should quit", quit_key);
+        }
+    }
+
+    #[test]
+    fn prompting_yesno() {
+        let (opts, buf_opts) = (one_page(), BufOptions::default());
+        let story = "{{ prompt_yesno : $ANSWER$ |,| y }}\nDone\n";
+        let narrator = StatefulStoryTeller::from_telling(teller("yesno", story, &opts));
+        let mut buf = TermBuffer::new(&buf_opts);
+        let narrator = tell_until(narrator, &mut buf, "Prompting (y/n)");
+
+        // Nothing typed yet, so p pauses, but it's part of "nope" once "no" is typed
+        let narrator = press_keys(narrator, &mut buf, "p");
+        assert_eq!(narrator.state_str(), "Paused");
+        let narrator = press_keys(narrator, &mut buf, "pnop");
+        assert_eq!(narrator.state_str(), "Prompting (y/n)");
+        let narrator = press_keys(narrator, &mut buf, "e\n");
+        let StatefulStoryTeller::Telling(st) = &narrator else {
+            panic!("answering should go back to telling");
+        };
+        assert_eq!(st.lookup("ANSWER").as_deref(), Some("n"));
+    }
+
+    #[test]
+    fn quitting_yesno() {
+        let (opts, buf_opts) = (one_page(), BufOptions::default());
+        let story = "{{ prompt_yesno : $ANSWER$ }}\nDone\n";
+        let narrator = StatefulStoryTeller::from_telling(teller("yesno_quit", story, &opts));
+        let mut buf = TermBuffer::new(&buf_opts);
+        let narrator = tell_until(narrator, &mut buf, "Prompting (y/n)");
+        let narrator = press_keys(narrator, &mut buf, "ye");
+        // q doesn't spell anything, so it quits, even partway through typing an answer
+        let StatefulStoryTeller::Quit(st) = press_keys(narrator, &mut buf, "q") else {
+            panic!("q should quit");
+        };
+        // and the story goes back to right before the question
+        assert_eq!(st.lookup("ANSWER"), None);
+        assert_eq!(st.story.get_place(), Default::default());
+    }
+
+    #[test]
+    fn choosing_from_menu() {
+        let (opts, buf_opts) = (one_page(), BufOptions::default());
+        let story = "{{ choice_menu :\n$PICK$\nleft\nright\n}}\nDone\n";
+        let narrator = StatefulStoryTeller::from_telling(teller("menu", story, &opts));
+        let mut buf = TermBuffer::new(&buf_opts);
+        let narrator = tell_until(narrator, &mut buf, "Choosing");
+
+        // Out of range choices don't count
+        let narrator = press_keys(narrator, &mut buf, "3\n");
+        assert_eq!(narrator.state_str(), "Choosing");
+        let narrator = press_keys(narrator, &mut buf, "\x7fp");
+        assert_eq!(narrator.state_str(), "Paused");
+        let narrator = press_keys(narrator, &mut buf, "p2\n");
+        let StatefulStoryTeller::Telling(st) = &narrator else {
+            panic!("choosing should go back to telling");
+        };
+        assert_eq!(st.lookup("PICK").as_deref(), Some("right"));
+
+        let narrator = StatefulStoryTeller::from_telling(teller("menu_quit", story, &opts));
+        let narrator = tell_until(narrator, &mut buf, "Choosing");
+        let narrator = press_keys(narrator, &mut buf, "1\x1b");
+        assert!(narrator.quit_early());
+    }
+
+    // Fetching a word that gets filled in by the test instead of another thread, so the test
+    // doesn't need the internet
+    fn fetching<'a>(
+        name: &str,
+        opts: &'a STOptions,
+        word: &Arc<Mutex<Option<Result<Option<String>>>>>,
+    ) -> StatefulStoryTeller<'a> {
+        let st = teller(name, "Done\n", opts);
+        let origin = Origin {
+            file: 0,
+            pos: Pos {
+                offset: 0,
+                line: 1,
+                col: 1,
+            },
+        };
+        st.start(Transition::FetchingWord(FetchingWord {
+            word: Arc::clone(word),
+            origin,
+            written: false,
+        }))
+    }
+
+    #[test]
+    fn fetching_word() {
+        let (opts, buf_opts) = (one_page(), BufOptions::default());
+        let mut buf = TermBuffer::new(&buf_opts);
+        let word = Arc::new(Mutex::new(None));
+        let mut narrator = fetching("fetch", &opts, &word);
+        narrator.step(&mut buf);
+        let narrator = press_keys(narrator, &mut buf, "x");
+        assert_eq!(narrator.state_str(), "Fetching a random word");
+
+        let mut narrator = press_keys(narrator, &mut buf, "p");
+        assert_eq!(narrator.state_str(), "Paused");
+        *word.lock().unwrap() = Some(Ok(Some("walrus".to_owned())));
+        // Nothing gets written while it's paused
+        narrator.step(&mut buf);
+        assert_eq!(buf.curr_line(), "");
+        let mut narrator = press_keys(narrator, &mut buf, "p");
+        narrator.step(&mut buf);
+        assert_eq!(buf.curr_line(), "walrus");
+        let narrator = narrator.press(None, &mut buf);
+        assert_eq!(narrator.state_str(), "Sleeping (1s)");
+
+        // Quitting doesn't have to wait to hear back
+        let narrator = fetching("fetch_quit", &opts, &Arc::new(Mutex::new(None)));
+        assert!(press_keys(narrator, &mut buf, "q").quit_early());
+    }
+
+    #[test]
+    fn waiting_for_kb() {
+        let (opts, buf_opts) = (one_page(), BufOptions::default());
+        let story = "{{ wait_kb : }}\nDone\n";
+        let mut buf = TermBuffer::new(&buf_opts);
+        // There's an earlier page to look back at, but b still just continues
+        buf.write_text("An earlier page");
+        buf.turn_page();
+        let narrator = StatefulStoryTeller::from_telling(teller("wait_b", story, &opts));
+        let narrator = tell_until(narrator, &mut buf, "Waiting for keybaord");
+        let narrator = press_keys(narrator, &mut buf, "b");
+        assert_eq!(narrator.state_str(), "Telling");
+        assert!(!buf.is_looking_back());
+
+        // There's a decision to go back to, but r doesn't rewind, and s doesn't save, either
+        for &(name, key) in &[("wait_r", "r"), ("wait_s", "s")] {
+            let mut st = teller(name, story, &opts);
+            st.remember_decision(&buf);
+            let narrator = StatefulStoryTeller::from_telling(st);
+            let narrator = tell_until(narrator, &mut buf, "Waiting for keybaord");
+            let narrator = press_keys(narrator, &mut buf, key);
+            assert_eq!(narrator.state_str(), "Telling", "{} should continue", key);
+        }
+    }
+
+    #[test]
+    fn backspacing_one_by_one() {
+        let (opts, buf_opts) = (one_page(), BufOptions::default());
+        let mut buf = TermBuffer::new(&buf_opts);
+        let backspacing = |name, unit| {
+            let bs = Backspacing {
+                unit,
+                num: 2,
+                pace: Duration::ZERO,
+            };
+            teller(name, "Done\n", &opts).start(Transition::Backspacing(bs))
+        };
+
+        // Each step erases one word, and the command only finishes once both are gone
+        buf.write_text("one two three");
+        let mut narrator = backspacing("erase_words", DisplayUnit::Word);
+        narrator.step(&mut buf);
+        assert_eq!(buf.curr_line(), "one two ");
+        let mut narrator = narrator.press(None, &mut buf);
+        assert_eq!(narrator.state_str(), "Backspacing");
+        narrator.step(&mut buf);
+        assert_eq!(buf.curr_line(), "one ");
+        let narrator = narrator.press(None, &mut buf);
+        assert_eq!(narrator.state_str(), "Telling");
+
+        // and the same for lines
+        buf.clear();
+        buf.write_text("ab\ncd\nef");
+        let mut narrator = backspacing("erase_lines", DisplayUnit::Line);
+        narrator.step(&mut buf);
+        assert_eq!(buf.get_cursor(), (2, 0));
+        let mut narrator = narrator.press(None, &mut buf);
+        narrator.step(&mut buf);
+        assert_eq!(buf.get_cursor(), (1, 0));
+        assert_eq!(narrator.press(None, &mut buf).state_str(), "Telling");
+    }
+
+    #[test]
+    fn rewind_to_decision() {
+        let (opts, buf_opts) = (STOptions::default(), BufOptions::default());
+        let mut st = teller("rewind", "Pick one\n#=$ left $=#\nWent left\n", &opts);
+        let mut buf = TermBuffer::new(&buf_opts);
+        buf.write_text("Pick one");
+        st.remember_decision(&buf);
+
+        st.set_val("ANSWER".to_owned(), "left".to_owned());
+        st.story.jump_to_section(Some(&"left".to_owned()));
+        st.story.advance(DisplayUnit::Word);
+        st.story.advance(DisplayUnit::Word);
+        buf.write_text(" and went left");
+        assert_eq!(st.story.curr_sect().get_name(), "left");
+
+        st.go_back_to(0, &mut buf).unwrap();
+        assert_eq!(st.story.curr_sect().get_name(), "Main Section");
+        assert_eq!(st.story.get_place(), Default::default());
+        assert_eq!(st.lookup("ANSWER"), None);
+        assert_eq!(buf.curr_line(), "Pick one");
+        // it gets remembered again when it's made again
+        assert!(st.decisions.is_empty());
+    }
+
+    #[test]
+    fn rewind_to_another_file() {
+        let (opts, buf_opts) = (STOptions::default(), BufOptions::default());
+        let path = scratch_stories(
+            "rewind_file",
+            &[("main.txt", "Pick one\n"), ("ch2.txt", "Chapter two\n")],
+        );
+        let mut st = StoryTeller::new(&path, 4).unwrap();
+        st.setup(&opts, None);
+        let mut buf = TermBuffer::new(&buf_opts);
+        buf.write_text("Pick one");
+        st.remember_decision(&buf);
+
+        st.story = st.story.load_neighbor("ch2.txt").unwrap();
+        st.go_back_to(0, &mut buf).unwrap();
+        assert_eq!(st.story.file_name(0), &path);
+        assert_eq!(st.story.get_place(), Default::default());
+
+        // The first file changed since, so there's no telling where going back would end up
+        st.remember_decision(&buf);
+        st.story = st.story.load_neighbor("ch2.txt").unwrap();
+        fs::write(&path, "Pick another\n").unwrap();
+        let err = st.go_back_to(0, &mut buf).unwrap_err();
+        assert!(matches!(err, RTError::StoryChanged(..)));
+        // and nothing changes when it can't go back
+        assert!(st.story.file_name(0).ends_with("ch2.txt"));
+        assert_eq!(st.decisions.len(), 1);
+    }
+}
@@ -1,43 +1,57 @@
+use std::sync::OnceLock;
+
 use regex::Regex;
 
 use either::Either;
 
-use script::token::Token;
+use script::token::{Pos, Token};
+
+use crate::commands::registry::{self, Call};
+use crate::err::Result;
+use crate::utils::{grapheme_width, text_width};
 
 #[derive(Debug, Clone)]
 pub enum Unit {
     Char(char),
     Word(String),
     WhiteSpace(String),
-    Special(Token), // Not Token::Text or Token::Char
+    Special(Token),           // Not Token::Text, Token::Char or Token::Command
+    Command(Box<Call>, bool), // (the command, whether to wait_for_kb afterwards)
 }
 
 impl Unit {
     // TODO: Figure out how to return an impl Iterator<Item=Unit>
-    pub fn from_token(tkn: Token) -> Vec<Unit> {
+    // pos is where tkn starts, and each returned Unit comes with where it starts. This fails if
+    // tkn is a command that doesn't make sense.
+    pub fn from_token(tkn: Token, pos: Pos) -> Result<Vec<(Unit, Pos)>> {
         use Unit::*;
-        match tkn {
+        let units = match tkn {
             Token::Text(s) => {
                 // This Regex explictly checks for a newline for dumb reasons
                 // Basically, extrace_page assumes that each Unit fits on a single line,
                 // so it really doesn't like something like WhiteSpace("\n\n") or WhiteSpace(" \n")
-                let re = Regex::new("(\n|[[\t ]]+)").expect("Typo if this does not work");
+                static RE: OnceLock<Regex> = OnceLock::new();
+                let re = RE.get_or_init(|| {
+                    Regex::new("(\n|[[\t ]]+)").expect("Typo if this does not work")
+                });
                 // Could I have made this any worse?
-                re.find_iter(&s)
+                let units: Vec<(Unit, usize)> = re
+                    .find_iter(&s)
                     .map(|mat| (mat.start(), mat.end(), mat.as_str()))
                     .chain(std::iter::once((0, 0, "SENTINEL")))
                     .scan(0, |word_start, mat| {
                         if (mat.1 - mat.0) != mat.2.len() {
                             if *word_start < s.len() {
-                                Some(Either::Left(std::iter::once(Word(
-                                    s[*word_start..].to_owned(),
+                                Some(Either::Left(std::iter::once((
+                                    Word(s[*word_start..].to_owned()),
+                                    *word_start,
                                 ))))
                             } else {
                                 None
                             }
                         } else {
-                            let unit1 = Word(s[*word_start..mat.0].to_owned());
-                            let unit2 = WhiteSpace(mat.2.to_owned());
+                            let unit1 = (Word(s[*word_start..mat.0].to_owned()), *word_start);
+                            let unit2 = (WhiteSpace(mat.2.to_owned()), mat.0);
                             *word_start = mat.1;
                             // there's a better way to do this. I just don't know it...
                             let iter = std::iter::once(unit1).chain(std::iter::once(unit2));
@@ -45,32 +59,59 @@ impl Unit {
                         }
                     })
                     .flatten()
+                    .collect();
+                // offsets only ever increase, so positions can be found in a single pass
+                let mut last = (0, pos);
+                units
+                    .into_iter()
+                    .map(|(unit, offset)| {
+                        last = (offset, last.1.advance(&s[last.0..offset]));
+                        (unit, last.1)
+                    })
                     .collect()
             }
-            Token::Char(c) => vec![Char(c)],
-            t => vec![Special(t)],
-        }
+            Token::Char(c) => vec![(Char(c), pos)],
+            // Comments are for the author, not the reader
+            Token::Comment(_) => vec![],
+            // The header is read by the story, not shown to the reader
+            Token::Header(_) => vec![],
+            Token::Command(func, args, wait) => {
+                let call = registry::parse(&func, &args)?;
+                vec![(Command(Box::new(call), wait), pos)]
+            }
+            t => vec![(Special(t), pos)],
+        };
+        Ok(units)
     }
     // basically len but keeps track of vertical spacing as well
-    pub fn area(&self) -> (usize, usize) {
+    // (measured in terminal columns, so e.g. Japanese text takes up twice as much room)
+    // col is where on the line the unit starts, which matters for how far a tab goes
+    pub fn area(&self, col: usize, tab_width: usize) -> (usize, usize) {
+        let tab_width = tab_width.max(1);
+        let tab = |col: usize| tab_width - col % tab_width;
         match self {
             Unit::Char('\0') => (0, 0),
             Unit::Char('\n') => (0, 1),
-            Unit::Char(_) => (1, 0),
+            Unit::Char('\t') => (tab(col), 0),
+            Unit::Char(c) => (grapheme_width(c.encode_utf8(&mut [0; 4])), 0),
             // words aren't allowed to have newline/space type characters in them
-            Unit::Word(w) => (w.chars().count() + 1, 0),
-            Unit::WhiteSpace(w) => w.chars().fold((0, 0), |acc, c| match c {
-                '\n' => (acc.0, acc.1 + 1),
-                '\0' => acc,
-                _ => (acc.0 + 1, acc.1),
-            }),
+            Unit::Word(w) => (text_width(w) + 1, 0),
+            Unit::WhiteSpace(w) => {
+                let (end, rows) = w.chars().fold((col, 0), |acc, c| match c {
+                    '\n' => (acc.0, acc.1 + 1),
+                    '\0' => acc,
+                    '\t' => (acc.0 + tab(acc.0), acc.1),
+                    _ => (acc.0 + 1, acc.1),
+                });
+                (end - col, rows)
+            }
+            // might need to depend on the command in the future
+            Unit::Command(..) => (0, 0),
             Unit::Special(t) => match t {
-                // might need to depend on the command in the future
-                Token::Command(..) => (0, 0),
-                Token::Variable(_) => (3, 0),
+                Token::Variable(_) | Token::VariableOr(..) => (3, 0),
                 // can't know variable length a priori so just guess
                 // ^^^^^^^ This is dumb. I should make pagination more dynamic at some point
-                Token::Symbol(s) => (s.len() + 2, 0),
+                Token::Symbol(s) => (text_width(s) + 2, 0),
                 _ => unreachable!(),
             },
         }
@@ -101,10 +142,10 @@ impl Unit {
         matches!(self, Unit::Word(_))
     }
     pub fn is_command(&self) -> bool {
-        matches!(self, Unit::Special(Token::Command(..)))
+        matches!(self, Unit::Command(..))
     }
     pub fn is_blocking_command(&self) -> bool {
-        matches!(self, Unit::Special(Token::Command(.., true)))
+        matches!(self, Unit::Command(_, true))
     }
     pub fn is_whitespace(&self) -> bool {
         matches!(self, Unit::WhiteSpace(..))
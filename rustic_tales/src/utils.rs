@@ -5,10 +5,13 @@ use std::{env, fs};
 
 use terminal_size::{terminal_size, Height, Width};
 
-use globset::{Glob, GlobSetBuilder};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 use crate::ansi::TermAction;
 use crate::err::{RTError, Result};
+use crate::storyteller::meta::StoryMeta;
 
 pub fn wait_for_enter(prompt: &str) {
     print!("{}", prompt);
@@ -25,6 +28,17 @@ pub fn terminal_dims() -> (u16, u16) {
     }
 }
 
+// How many terminal columns a single grapheme cluster takes up. Anything printable gets at least
+// one (a lone accent still shows up) and nothing gets more than two (ZWJ emoji are one glyph).
+pub fn grapheme_width(g: &str) -> usize {
+    g.width().clamp(1, 2)
+}
+
+// How many terminal columns some text takes up, assuming it has no newlines in it
+pub fn text_width(s: &str) -> usize {
+    s.graphemes(true).map(grapheme_width).sum()
+}
+
 pub fn no_term_echo() -> Option<termios::Termios> {
     use termios::*;
     let stdin_fd = stdin().as_raw_fd();
@@ -58,24 +72,29 @@ pub fn get_kb() -> Option<u8> {
     new_termios.c_cc[VMIN] = 0;
     new_termios.c_cc[VTIME] = 0;
     tcsetattr(stdin_fd, TCSANOW, &new_termios).ok()?;
-    let res = stdin().bytes().next().and_then(|res| res.ok());
+    let res = stdin().lock().bytes().next().and_then(|res| res.ok());
     tcsetattr(stdin_fd, TCSANOW, &orig_termios).ok()?;
     res
 }
 
 // Don't tell anyone I wrote a spinlock, ok?
-pub fn wait_for_kb() {
-    while get_kb() == None {}
+// Returns the key that got pressed
+pub fn wait_for_kb() -> u8 {
+    loop {
+        if let Some(key) = get_kb() {
+            return key;
+        }
+    }
 }
 
-pub fn wait_for_kb_with_prompt(prompt: &str) {
+pub fn wait_for_kb_with_prompt(prompt: &str) -> u8 {
     print!("{}", prompt);
     let _ = std::io::stdout().flush();
-    wait_for_kb();
+    wait_for_kb()
 }
 
 pub fn exhaust_kb() {
-    while get_kb() != None {}
+    while get_kb().is_some() {}
 }
 
 pub fn get_user() -> Option<String> {
@@ -103,18 +122,7 @@ pub fn menu(
     if clear {
         clear_screen();
     }
-    let globs = ignore_patterns.and_then(|patts| {
-        patts
-            .iter()
-            .fold(GlobSetBuilder::new(), |mut builder, pat| {
-                if let Ok(glob) = Glob::new(pat) {
-                    builder.add(glob);
-                }
-                builder
-            })
-            .build()
-            .ok()
-    });
+    let globs = ignore_patterns.and_then(build_globs);
 
     // This wastes some space, but I expect items.len() < 20 in practice, so who cares?
     let mut true_indices = Vec::with_capacity(items.len());
@@ -123,7 +131,7 @@ pub fn menu(
         .enumerate()
         .filter(|(_, item)| {
             let s = item.as_ref();
-            globs.as_ref().map_or(true, |gs| !gs.is_match(s))
+            !globs.as_ref().is_some_and(|gs| gs.is_match(s))
         })
         .enumerate()
     {
@@ -145,11 +153,36 @@ pub fn menu(
     }
 }
 
-pub fn choose_story(ignore_patterns: &[String], folder: &str) -> Result<String> {
+fn build_globs(patterns: &[String]) -> Option<GlobSet> {
+    patterns
+        .iter()
+        .fold(GlobSetBuilder::new(), |mut builder, pat| {
+            if let Ok(glob) = Glob::new(pat) {
+                builder.add(glob);
+            }
+            builder
+        })
+        .build()
+        .ok()
+}
+
+// Every story in folder, skipping the ones that match an ignore pattern
+pub fn all_stories(ignore_patterns: &[String], folder: &str) -> Result<Vec<String>> {
+    let globs = build_globs(ignore_patterns);
+    let mut stories: Vec<_> = story_files(folder)?
+        .into_iter()
+        .filter(|s| !globs.as_ref().is_some_and(|gs| gs.is_match(s)))
+        .map(|s| format!("{}/{}", folder, s))
+        .collect();
+    stories.sort();
+    Ok(stories)
+}
+
+fn story_files(folder: &str) -> Result<Vec<String>> {
     let mut dir = env::current_dir()?;
     dir.push(folder);
 
-    let stories: Vec<String> = fs::read_dir(dir)?
+    Ok(fs::read_dir(dir)?
         .filter_map(|e| e.ok())
         .filter(|e| {
             e.file_type()
@@ -158,7 +191,44 @@ pub fn choose_story(ignore_patterns: &[String], folder: &str) -> Result<String>
                 .is_some()
         })
         .filter_map(|e| e.file_name().into_string().ok())
+        .collect())
+}
+
+pub fn choose_story(ignore_patterns: &[String], folder: &str) -> Result<String> {
+    let stories = all_stories(ignore_patterns, folder)?;
+    // Stories with a broken header still get listed, so picking them says what's wrong
+    let labels: Vec<_> = stories
+        .iter()
+        .map(|path| {
+            let file_name = &path[folder.len() + 1..];
+            StoryMeta::from_file(path)
+                .map(|meta| meta.label(file_name))
+                .unwrap_or_else(|_| file_name.to_owned())
+        })
         .collect();
-    let file_name = &stories[menu(&stories, Some(ignore_patterns), true)?];
-    Ok(format!("{}/{}", folder, file_name))
+    let idx = menu(&labels, None, true)?;
+    Ok(stories[idx].clone())
+}
+
+// A fresh, empty folder for a test to write files into
+#[cfg(test)]
+pub fn scratch_dir(name: &str) -> std::path::PathBuf {
+    let dir = env::temp_dir().join(format!("rustic_tales_{}_{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("temp dir should be writable");
+    dir
+}
+
+// Writes each (file name, contents) into a fresh folder, and returns the path of the first one
+#[cfg(test)]
+pub fn scratch_stories(name: &str, files: &[(&str, &str)]) -> String {
+    let dir = scratch_dir(name);
+    for (file, contents) in files {
+        let path = dir.join(file);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("temp dir should be writable");
+        }
+        fs::write(path, contents).expect("temp dir should be writable");
+    }
+    dir.join(files[0].0).display().to_string()
 }
@@ -0,0 +1,48 @@
+use std::fs;
+
+use rustic_tales::buffer::TermBuffer;
+use rustic_tales::err::{RTError, Result};
+use rustic_tales::options::{BufOptions, STOptions};
+use rustic_tales::storyteller::{StatefulStoryTeller, Telling};
+use rustic_tales::{parse_arg, register, Arg, Arity, StoryCommand, StoryTeller, Transition};
+
+// Says its arguments louder
+#[derive(Debug)]
+struct Shout(Vec<Arg<String>>);
+
+impl StoryCommand for Shout {
+    fn parse(func: &'static str, args: &[String]) -> Result<Self> {
+        let args = (0..args.len()).map(|i| parse_arg(func, args, i));
+        Ok(Shout(args.collect::<Result<_>>()?))
+    }
+    fn run(&self, st: &mut StoryTeller<Telling>, buf: &mut TermBuffer) -> Result<Transition> {
+        let words = self.0.iter().map(|arg| st.eval(arg));
+        let words = words.collect::<Result<Vec<_>>>()?;
+        buf.write_text(&words.join(" ").to_uppercase());
+        Ok(Transition::Nothing)
+    }
+}
+
+#[test]
+fn register_and_tell() {
+    let dir = std::env::temp_dir().join(format!("rustic_tales_{}_shout", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("shout.txt");
+    fs::write(&path, "{{ shout : big |,| ${{WHO|world}} }}\n").unwrap();
+
+    // Stories can't use it until it's registered
+    let (st_opts, buf_opts) = (STOptions::default(), BufOptions::default());
+    match StoryTeller::<Telling>::new(&path, buf_opts.tab_width) {
+        Err(RTError::AtPos(_, e)) => assert!(matches!(*e, RTError::UnrecognizedCommand(_))),
+        other => panic!("expected an unknown command, not {:?}", other.map(|_| ())),
+    }
+    register::<Shout>("shout", Arity::at_least(1));
+    let mut st = StoryTeller::<Telling>::new(&path, buf_opts.tab_width).unwrap();
+    st.setup(&st_opts, None);
+    let mut buf = TermBuffer::new(&buf_opts);
+    let mut narrator = StatefulStoryTeller::from_telling(st);
+    while !narrator.step(&mut buf).story_ended() {
+        narrator = narrator.transition(&mut buf);
+    }
+    assert_eq!(buf.curr_line(), "BIG WORLD");
+}
@@ -1,9 +1,21 @@
 extern crate regex;
+#[macro_use]
 pub mod token;
+pub mod lint;
+pub mod print;
 
 #[cfg(test)]
+#[allow(clippy::needless_borrow)]
 mod tests {
-    use crate::token::{tokenize, Token};
+    use std::fs;
+    use std::io::BufReader;
+    use std::path::Path;
+
+    use crate::lint::near_misses;
+    use crate::print::print;
+    use crate::token::{
+        escape_text, tokenize, tokenize_reader, tokenize_spanned, Pos, Token, TokenStream,
+    };
 
     #[test]
     fn basic_tokenization() {
@@ -76,7 +88,29 @@ mod tests {
         );
     }
 
+    // Every book in corpus/ should come out as one big piece of text, exactly as written
     #[test]
+    fn tokenize_corpus() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus");
+        let mut num_books = 0;
+        for entry in fs::read_dir(dir).expect("the corpus should be next to Cargo.toml") {
+            let path = entry.expect("could not read the corpus").path();
+            let book = fs::read_to_string(&path).expect("books should be utf-8");
+            assert_eq!(
+                tokenize(&book),
+                vec![Token::Text(book.clone())],
+                "{}",
+                path.display()
+            );
+            num_books += 1;
+        }
+        assert!(num_books > 0);
+    }
+
+    // This needs the internet, so it only runs with `cargo test -- --ignored`. tokenize_corpus
+    // checks the same thing offline.
+    #[test]
+    #[ignore]
     fn tokenize_gutenberg() -> reqwest::Result<()> {
         extern crate reqwest;
 
@@ -167,6 +201,205 @@ mod tests {
         );
     }
 
+    #[test]
+    fn tokenize_comments() {
+        let test = "#~ This whole line is a note for the author\n\
+                          Once upon a time{{~ TODO: pick a better opening ~}}, the end.\n\
+                          {{~ Credits:\n\
+                          * everything by me\n\
+                          ~}}\n\
+                          Not #~ a comment unless it starts the line\n\
+                          #~ last line";
+        assert_eq!(
+            tokenize(test),
+            vec![
+                Token::Comment(" This whole line is a note for the author".to_owned()),
+                Token::Text("Once upon a time".to_owned()),
+                Token::Comment(" TODO: pick a better opening ".to_owned()),
+                Token::Text(", the end.\n".to_owned()),
+                Token::Comment(" Credits:\n* everything by me\n".to_owned()),
+                Token::Text("\nNot #~ a comment unless it starts the line\n".to_owned()),
+                Token::Comment(" last line".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_escapes() {
+        let test = "Write {{\"${{NAME}}\"}} to print a variable and {{\"{{ cmd : args }}\"}} to run a command.\n\
+                    Pages end with {{\"/PAGE/\"}}, which is not the same as {/}PAGE/.";
+        assert_eq!(
+            tokenize(test),
+            vec![
+                Token::Text(
+                    "Write ${{NAME}} to print a variable and {{ cmd : args }} to run a command.\n\
+                     Pages end with /PAGE/, which is not the same as "
+                        .to_owned()
+                ),
+                Token::Char('/'),
+                Token::Text("PAGE/.".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn escape_text_round_trip() {
+        let tests = [
+            "nothing special here",
+            "${{VAR}} and $sym$",
+            "{{ cmd : arg }}\nmore",
+            "/PAGE/",
+            "#=$ section $=#\nstuff",
+            "#~ not a comment\n",
+            "{{~ not a comment either ~}}",
+            "{c}",
+            "quotes: {{\"already escaped\"}}",
+            "the hard one: \"}} and \"}}\"}} again",
+            "ends with a quote \"",
+        ];
+        for &test in &tests {
+            let escaped = escape_text(test);
+            assert_eq!(tokenize(&escaped), vec![Token::Text(test.to_owned())]);
+        }
+        assert_eq!(escape_text(tests[0]), tests[0]);
+    }
+
+    #[test]
+    fn tokenize_block_commands() {
+        let test = "Where to?\n\
+                    {{ choice_menu :\n\
+                    \x20   $WHERE$\n\
+                    \x20   Deeper into the cave\n\
+                    \x20   Back the way I came\n\
+                    : wait_for_kb }}\n\
+                    You chose ${{WHERE}}.";
+        assert_eq!(
+            tokenize_spanned(test),
+            vec![
+                (Token::Text("Where to?\n".to_owned()), Pos::default()),
+                (
+                    Token::Command(
+                        "choice_menu".to_owned(),
+                        vec![
+                            "$WHERE$".to_owned(),
+                            "Deeper into the cave".to_owned(),
+                            "Back the way I came".to_owned(),
+                        ],
+                        true,
+                    ),
+                    Pos {
+                        offset: 10,
+                        line: 2,
+                        col: 1
+                    }
+                ),
+                (
+                    Token::Text("You chose ".to_owned()),
+                    Pos {
+                        offset: 105,
+                        line: 7,
+                        col: 1
+                    }
+                ),
+                (
+                    Token::Variable("WHERE".to_owned()),
+                    Pos {
+                        offset: 115,
+                        line: 7,
+                        col: 11
+                    }
+                ),
+                (
+                    Token::Text(".".to_owned()),
+                    Pos {
+                        offset: 125,
+                        line: 7,
+                        col: 21
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_across_lines() {
+        let test = "{{{\ntitle : Lines\n}}}\n\
+                    A {{ stray : brace }} or two {{ and : a block\n\
+                    #=$ sect $=#\n\
+                    {{~ a comment\n\
+                    across }} lines ~}}\n\
+                    {{\"verbatim\n${{NOT_A_VAR}}\"}} and ${{A_VAR|default}}\n\
+                    {{ choice_menu :\n\
+                    \x20   $X$\n\
+                    \x20   \"}}\"\n\
+                    }}\n\
+                    {{~ never closed\n\
+                    #~ but this is\n\
+                    the end {c}";
+        assert_eq!(
+            tokenize(test),
+            vec![
+                Token::Header(vec![("title".to_owned(), "Lines".to_owned())]),
+                Token::Text("A {{ stray : brace }} or two {{ and : a block\n".to_owned()),
+                Token::SectionStart("sect".to_owned()),
+                Token::Comment(" a comment\nacross }} lines ".to_owned()),
+                Token::Text("\nverbatim\n${{NOT_A_VAR}} and ".to_owned()),
+                Token::VariableOr("A_VAR".to_owned(), "default".to_owned()),
+                Token::Text("\n".to_owned()),
+                Token::Command(
+                    "choice_menu".to_owned(),
+                    vec!["$X$".to_owned(), "}}".to_owned()],
+                    false
+                ),
+                Token::Text("{{~ never closed\n".to_owned()),
+                Token::Comment(" but this is".to_owned()),
+                Token::Text("the end ".to_owned()),
+                Token::Char('c'),
+            ]
+        );
+
+        // Reading it a few bytes at a time shouldn't change anything, even when a block or
+        // comment (or a character) gets split between reads
+        let test = format!("{}\n{{{{ pause : ½s }}}}é\n", test);
+        let spanned = tokenize_spanned(&test);
+        for &chunk in &[1, 2, 3, 7, 64] {
+            let reader = BufReader::with_capacity(chunk, test.as_bytes());
+            let streamed: Vec<_> = tokenize_reader(reader).collect();
+            assert_eq!(streamed, spanned, "{} bytes at a time", chunk);
+        }
+
+        // tokenize_reader just stops if reading fails, but TokenStream says why
+        let invalid = &b"fine\n\xff\xfe\n"[..];
+        assert_eq!(tokenize_reader(invalid).count(), 0);
+        assert!(TokenStream::new(invalid).any(|res| res.is_err()));
+    }
+
+    #[test]
+    fn tokenize_header() {
+        let test = "{{{\n\
+                          title : Hello\n\
+                          content_warnings : none |,| really\n\
+                          }}}\n\
+                          Hello ${{USER_NAME}}!\n\
+                          {{{\n\
+                          title : only the start of a story has a header\n\
+                          }}}";
+        assert_eq!(
+            tokenize(test),
+            vec![
+                Token::Header(vec![
+                    ("title".to_owned(), "Hello".to_owned()),
+                    ("content_warnings".to_owned(), "none |,| really".to_owned()),
+                ]),
+                Token::Text("Hello ".to_owned()),
+                Token::Variable("USER_NAME".to_owned()),
+                Token::Text(
+                    "!\n{{{\ntitle : only the start of a story has a header\n}}}".to_owned()
+                ),
+            ]
+        );
+    }
+
     #[test]
     fn tokenization_newline_after_command() {
         let test = "Stuff and things and things and stuff\n\
@@ -186,4 +419,215 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn tokenize_with_positions() {
+        let test = "Is your favorite color red?\n\
+                    {{ prompt_yesno : $ANSWER$ }}\n\
+                    \n\
+                    #=$ yes section $=#\n\
+                    Très ${{RED_LFG}}bien{!}";
+        let pos = |offset, line, col| Pos { offset, line, col };
+        assert_eq!(
+            tokenize_spanned(test),
+            vec![
+                (
+                    Token::Text("Is your favorite color red?\n".to_owned()),
+                    pos(0, 1, 1)
+                ),
+                (
+                    Token::Command(
+                        "prompt_yesno".to_owned(),
+                        vec!["$ANSWER$".to_owned()],
+                        false
+                    ),
+                    pos(28, 2, 1)
+                ),
+                (Token::Text("\n".to_owned()), pos(58, 3, 1)),
+                (Token::SectionStart("yes section".to_owned()), pos(59, 4, 1)),
+                (Token::Text("Très ".to_owned()), pos(79, 5, 1)),
+                (Token::Variable("RED_LFG".to_owned()), pos(85, 5, 6)),
+                (Token::Text("bien".to_owned()), pos(97, 5, 18)),
+                (Token::Char('!'), pos(101, 5, 22)),
+            ]
+        );
+    }
+
+    #[test]
+    fn print_round_trip() {
+        let tests = [
+            "{{{\ntitle : A Story\nauthor: someone\n}}}\nOnce upon a time, ${{NAME|you}} found a {x}.\n/PAGE/",
+            "{{seems:legal|,|enough}}\n{{ wait_kb :}}\n{{ x :\n: wait_for_kb }}\nhi",
+            "{{ cmd : \"time: 2am\" |,| \"\" |,| \"  padded \" |,| a \"|,|\" b : wait_for_kb }}\n",
+            "text #=$ a section $=#\nmore $sym$ and {{\"$not$ a {symbol}\"}} or a$b$c$",
+            "#~ a line comment\n\n{{~ a block comment ~}}\nx {{~ another\none ~}} y",
+            "#~ has ~}} in it\nx{{\"{\"}}{c}",
+            "{{ choice_menu :\n    $ANSWER$\n    \"}}\"\n    \": wait_for_kb }}\"\n}}\n",
+        ];
+        for &test in &tests {
+            let tkns = tokenize(test);
+            assert_eq!(tokenize(&print(&tkns)), tkns, "printing {:?}", test);
+        }
+    }
+
+    #[test]
+    fn print_canonical_commands() {
+        let print_src = |src: &str| print(&tokenize(src));
+        assert_eq!(
+            print_src("{{seems:legal|,|enough}}\n{{ backspace: 10 |,| chars : wait_for_kb }}"),
+            "{{ seems : legal |,| enough }}\n{{ backspace : 10 |,| chars : wait_for_kb }}\n"
+        );
+        assert_eq!(print_src("{{wait_kb :}}\n"), "{{ wait_kb :}}\n");
+        assert_eq!(
+            print_src("{{ wait_kb :\n: wait_for_kb }}"),
+            "{{ wait_kb :\n: wait_for_kb }}\n"
+        );
+        assert_eq!(
+            print_src("{{ jump_if_eq : ${{X}} |,| \"a: b\" |,| sect }}"),
+            "{{ jump_if_eq : ${{X}} |,| \"a: b\" |,| sect }}\n"
+        );
+        // Long commands get split up
+        let long = format!("{{{{ choice_menu : $A$ |,| {} |,| no }}}}", "y".repeat(80));
+        assert_eq!(
+            print_src(&long),
+            format!(
+                "{{{{ choice_menu :\n    $A$\n    {}\n    no\n}}}}\n",
+                "y".repeat(80)
+            )
+        );
+    }
+
+    #[test]
+    fn near_miss_warnings() {
+        let test = "Hi {{ no colon }} and {{ANSWER}}\n\
+                    {{ cmd : a }} trailing\n\
+                    ${{ spaced }} /page/ {{\"{{ escaped }} /page/\"}}\n\
+                    x #~ not a comment\n\
+                    #=$ 3 $=#\n\
+                    {{ menu :\n\
+                    \x20   never closed\n\
+                    $fine$ {c} ${{FINE}} /PAGE/";
+        let found: Vec<_> = near_misses(test)
+            .into_iter()
+            .map(|(pos, _)| (pos.line, pos.col))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (1, 4),
+                (1, 23),
+                (2, 1),
+                (3, 1),
+                (3, 15),
+                (4, 3),
+                (5, 1),
+                (6, 1)
+            ]
+        );
+        assert!(near_misses("{{{\ntitle : fine\n}}}\n{{ cmd : fine }}\n").is_empty());
+    }
+}
+
+// Invariants that should hold no matter what a story has in it
+#[cfg(test)]
+mod properties {
+    use proptest::prelude::*;
+    use proptest::sample::{select, Index};
+
+    use crate::print::print;
+    use crate::token::{tokenize, tokenize_spanned, Pos, Token};
+
+    // Bits of syntax (and things that almost are), plus multi-byte chars to put next to them
+    const PIECES: [&str; 36] = [
+        "{",
+        "}",
+        "{{",
+        "}}",
+        "{{{\n",
+        "}}}",
+        "$",
+        "${{",
+        "|",
+        "|,|",
+        ":",
+        " : ",
+        "wait_for_kb",
+        "/",
+        "/PAGE/",
+        "#",
+        "#=$ ",
+        " $=#",
+        "#~",
+        "{{~",
+        "~}}",
+        "{{\"",
+        "\"}}",
+        "\"",
+        "\\",
+        "\n",
+        " ",
+        "\t",
+        "é",
+        "日本",
+        "🦀",
+        "\u{301}",
+        "a",
+        "cmd",
+        "VAR",
+        "{x}",
+    ];
+    const BOOKS: [&str; 4] = [
+        include_str!("../corpus/alice.txt"),
+        include_str!("../corpus/field_notes.txt"),
+        include_str!("../corpus/household_accounts.txt"),
+        include_str!("../corpus/pride_and_prejudice.txt"),
+    ];
+
+    // Mostly syntax, so that the interesting cases actually come up
+    fn script_like() -> impl Strategy<Value = String> {
+        let piece = prop_oneof![
+            3 => select(PIECES.to_vec()).prop_map(str::to_owned),
+            1 => "[a-zé日🦀 ]{1,4}",
+        ];
+        prop::collection::vec(piece, 0..40).prop_map(|pieces| pieces.concat())
+    }
+    fn any_story() -> impl Strategy<Value = String> {
+        prop_oneof![script_like(), any::<String>()]
+    }
+
+    proptest! {
+        #[test]
+        fn positions_line_up(story in any_story()) {
+            let mut prev = None;
+            for (_, pos) in tokenize_spanned(&story) {
+                prop_assert!(story.is_char_boundary(pos.offset));
+                prop_assert_eq!(pos, Pos::default().advance(&story[..pos.offset]));
+                prop_assert!(prev < Some(pos.offset));
+                prev = Some(pos.offset);
+            }
+        }
+
+        #[test]
+        fn print_round_trip(story in any_story()) {
+            let tkns = tokenize(&story);
+            prop_assert_eq!(tokenize(&print(&tkns)), tkns);
+        }
+
+        #[test]
+        fn prose_is_text(prose in "[^{$/#]*") {
+            let expected = if prose.is_empty() { vec![] } else { vec![Token::Text(prose.clone())] };
+            prop_assert_eq!(tokenize(&prose), expected.clone());
+            prop_assert_eq!(tokenize(&print(&expected)), expected);
+        }
+
+        // Books are long enough that there's a lot of context for a bit of syntax to interact with
+        #[test]
+        fn syntax_in_a_book(book in select(BOOKS.to_vec()), at in any::<Index>(), piece in script_like()) {
+            let chars: Vec<_> = book.char_indices().map(|(i, _)| i).collect();
+            let at = chars[at.index(chars.len())];
+            let story = format!("{}{}{}", &book[..at], piece, &book[at..]);
+            let tkns = tokenize(&story);
+            prop_assert_eq!(tokenize(&print(&tkns)), tkns);
+        }
+    }
 }
@@ -0,0 +1,85 @@
+use regex::Regex;
+
+use crate::token::{parse_verbatim, tokenize_spanned, Pos, Token};
+
+// Things in the text of a story that look like they were meant to be special syntax, but were
+// written slightly wrong (e.g. `{{ no colon }}`), so they'd otherwise quietly show up as text.
+// Returns where each one starts, along with what seems to be wrong.
+pub fn near_misses(stream: &str) -> Vec<(Pos, String)> {
+    let tkns = tokenize_spanned(stream);
+    let mut ret = Vec::new();
+    for (i, (tkn, pos)) in tkns.iter().enumerate() {
+        if let Token::Text(_) = tkn {
+            let end = tkns
+                .get(i + 1)
+                .map_or(stream.len(), |(_, next)| next.offset);
+            check_text(&stream[pos.offset..end], *pos, &mut ret);
+        }
+    }
+    ret
+}
+
+// source is exactly what some text was written as (so escaped text still has its {{" "}})
+fn check_text(source: &str, start: Pos, misses: &mut Vec<(Pos, String)>) {
+    let mut idx = 0;
+    while let Some(i) = source[idx..].find(['{', '$', '#', '/']) {
+        idx += i;
+        let rest = &source[idx..];
+        if let Some((_, len)) = parse_verbatim(rest) {
+            // Anything goes in here
+            idx += len;
+            continue;
+        }
+        if let Some(msg) = near_miss(rest, start.offset + idx == 0) {
+            misses.push((start.advance(&source[..idx]), msg));
+        }
+        // The '{' in "${{" would just get reported again
+        idx += if rest.starts_with("${{") { 3 } else { 1 };
+    }
+}
+
+// What's wrong with the thing at the start of stream, if it looks like it was meant to be special
+fn near_miss(stream: &str, at_start: bool) -> Option<String> {
+    let msg = if stream.starts_with("{{\"") {
+        "escaped text needs to end with '\"}}'"
+    } else if stream.starts_with("{{~") {
+        "comments that start with '{{~' need to end with '~}}'"
+    } else if stream.starts_with("{{{") {
+        if at_start {
+            "every line in a header needs to look like 'key : value', and the header needs to end with '}}}' on a line of its own"
+        } else {
+            "headers ('{{{') can only go at the very start of a story"
+        }
+    } else if stream.starts_with("${{") {
+        "variables look like '${{NAME}}' (or '${{NAME|default}}'), and names can't have spaces or braces in them"
+    } else if stream.starts_with("{{") {
+        let line = stream.lines().next().unwrap_or_default();
+        let looks_like_var = regex!(r"^\{\{[^:\{\}]*\}\}", "var or cmd?");
+        let looks_like_cmd = regex!(r"^\{\{[[:space:]]*\w+[[:space:]]*:", "is it a command");
+        if looks_like_var.is_match(line) {
+            "this is missing either the ':' after a command's name or the '$' before a variable"
+        } else if !looks_like_cmd.is_match(line) {
+            "'{{' should start a command (or '${{' a variable), but this doesn't look like either"
+        } else if line
+            .find("}}")
+            .is_some_and(|i| !line[i + 2..].trim().is_empty())
+        {
+            "a command has to be the last thing on its line"
+        } else {
+            "this looks like a command, but its arguments could not be read (is a '}}' or a quote missing?)"
+        }
+    } else if stream.starts_with("#=$") {
+        "sections start with '#=$ name $=#' on a line of their own, and the name can't be a number"
+    } else if stream.starts_with("#~") {
+        // At the start of a line, this would have been a comment
+        "comments that start with '#~' have to be at the start of a line"
+    } else if stream
+        .get(..6)
+        .is_some_and(|s| s.eq_ignore_ascii_case("/PAGE/"))
+    {
+        "pages end with '/PAGE/', in capital letters"
+    } else {
+        return None;
+    };
+    Some(msg.to_owned())
+}
@@ -0,0 +1,110 @@
+use crate::token::{escape_text, quote_arg, tokenize, verbatim, Token};
+
+// Commands longer than this put one argument per line
+const MAX_COMMAND_LEN: usize = 80;
+
+// Turns tokens back into Script, written the canonical way (e.g. `{{ cmd : a |,| b }}` no matter
+// how it was spaced originally). Anything that came out of tokenize comes back the same, i.e.
+// tokenize(&print(&tokenize(s))) == tokenize(s).
+pub fn print(tkns: &[Token]) -> String {
+    let out = Printer::new(false).print(tkns);
+    if tokenize(&out) == tkns {
+        out
+    } else {
+        // Text can accidentally run together with the tokens around it (e.g. "a$b" before
+        // $sym$), so play it safe and escape any text that could possibly be part of something.
+        // How comments get written can also matter to the tokens after them.
+        Printer::new(true).print(tkns)
+    }
+}
+
+struct Printer {
+    out: String,
+    // escape text with anything special looking in it, even if it's fine alone (and write
+    // comments on their own lines whenever possible)
+    strict: bool,
+}
+
+impl Printer {
+    fn new(strict: bool) -> Self {
+        Printer {
+            out: String::new(),
+            strict,
+        }
+    }
+    fn at_line_start(&self) -> bool {
+        self.out.is_empty() || self.out.ends_with('\n')
+    }
+
+    fn print(mut self, tkns: &[Token]) -> String {
+        for tkn in tkns {
+            self.print_token(tkn);
+        }
+        self.out
+    }
+    fn print_token(&mut self, tkn: &Token) {
+        match tkn {
+            Token::Text(s) => {
+                // (a '}' can close something that started in the token before)
+                let special: &[char] = &['{', '}', '$', '/', '#'];
+                if self.strict && s.contains(special) {
+                    // escape_text only escapes things when it has to, so force it
+                    self.out += &verbatim(s);
+                } else {
+                    self.out += &escape_text(s);
+                }
+            }
+            Token::Command(name, args, wait) => self.print_command(name, args, *wait),
+            Token::Variable(v) => self.out += &format!("${{{{{}}}}}", v),
+            Token::VariableOr(v, def) => self.out += &format!("${{{{{}|{}}}}}", v, def),
+            Token::Symbol(s) => self.out += &format!("${}$", s),
+            Token::PageEnd => self.out += "/PAGE/",
+            Token::Char(c) => self.out += &format!("{{{}}}", c),
+            Token::SectionStart(name) => self.out += &format!("#=$ {} $=#\n", name),
+            Token::Comment(note) => {
+                // There's no telling which kind of comment this was, but block comments are
+                // usually written {{~ like this ~}}. Line comments are pickier about where they
+                // go, so when in doubt (i.e. strict), use those whenever possible.
+                let looks_like_line = self.strict || !note.ends_with(char::is_whitespace);
+                let line = self.at_line_start() && !note.contains('\n');
+                if line && (looks_like_line || note.contains("~}}")) {
+                    self.out += &format!("#~{}\n", note);
+                } else {
+                    self.out += &format!("{{{{~{}~}}}}", note);
+                }
+            }
+            Token::Header(fields) => {
+                self.out += "{{{\n";
+                for (key, val) in fields {
+                    self.out += &format!("{} : {}\n", key, val);
+                }
+                // A header ends at the first "}}}" at the end of a line, so one at the end of the
+                // last value has to share its line with the real end
+                if fields.last().is_some_and(|(_, val)| val.ends_with("}}}")) {
+                    self.out.pop();
+                }
+                self.out += "}}}\n";
+            }
+        }
+    }
+    // Commands eat the newline after them, so that gets printed too
+    fn print_command(&mut self, name: &str, args: &[String], wait: bool) {
+        let args: Vec<_> = args.iter().map(|arg| quote_arg(arg)).collect();
+        let close = if wait { ": wait_for_kb }}" } else { "}}" };
+        let inline = format!("{{{{ {} : {} {}", name, args.join(" |,| "), close);
+        if args.is_empty() && !wait {
+            self.out += &format!("{{{{ {} :}}}}\n", name);
+        } else if !args.is_empty() && inline.len() <= MAX_COMMAND_LEN {
+            self.out += &inline;
+            self.out += "\n";
+        } else {
+            // (this is also the only way to wait after a command with no arguments)
+            self.out += &format!("{{{{ {} :\n", name);
+            for arg in args {
+                self.out += &format!("    {}\n", arg);
+            }
+            self.out += close;
+            self.out += "\n";
+        }
+    }
+}
@@ -1,14 +1,73 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+
 use regex::Regex;
 
+// Compiles a regex the first time it's used and reuses it after that, since compiling
+// one for every special character in a book adds up
+macro_rules! regex {
+    ($re:expr, $msg:expr) => {{
+        static RE: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+        RE.get_or_init(|| Regex::new($re).expect($msg))
+    }};
+}
+
+// Where in the original source something begins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pos {
+    pub offset: usize, // in bytes
+    pub line: usize,   // starts at 1
+    pub col: usize,    // starts at 1, counted in chars
+}
+
+impl Default for Pos {
+    fn default() -> Self {
+        Pos {
+            offset: 0,
+            line: 1,
+            col: 1,
+        }
+    }
+}
+
+impl Pos {
+    // The position immediately after `s`, assuming `s` starts at `self`
+    pub fn advance(self, s: &str) -> Pos {
+        s.chars().fold(
+            Pos {
+                offset: self.offset + s.len(),
+                ..self
+            },
+            |pos, c| {
+                if c == '\n' {
+                    Pos {
+                        line: pos.line + 1,
+                        col: 1,
+                        ..pos
+                    }
+                } else {
+                    Pos {
+                        col: pos.col + 1,
+                        ..pos
+                    }
+                }
+            },
+        )
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Token {
-    Text(String),                       // blah
+    Text(String),                       // blah (or {{" blah "}} to escape special syntax)
     Command(String, Vec<String>, bool), // {{ cmd : arg1 |,| arg2 |,| ... : wait_for_kb? }}
     Variable(String),                   // ${{var}}
+    VariableOr(String, String),         // ${{var|default}} (default is used when var is unset)
     Symbol(String),                     // $sym$
     PageEnd,                            // /PAGE/
     Char(char),                         // {c}
     SectionStart(String),               // #=$ section_name $=#
+    Comment(String),                    // #~ note (whole line) or {{~ note ~}}
+    Header(Vec<(String, String)>),      // {{{\n key : value \n ... }}} (only at the very start)
 }
 
 impl Token {
@@ -21,23 +80,32 @@ impl Token {
     pub fn is_sect_start(&self) -> bool {
         matches!(self, Token::SectionStart(_))
     }
+    pub fn is_comment(&self) -> bool {
+        matches!(self, Token::Comment(_))
+    }
     pub fn is_empty(&self) -> bool {
         match self {
             Token::Text(s) => s.is_empty(),
             Token::Command(c, ..) => c.is_empty(),
             Token::Variable(v) => v.is_empty(),
+            Token::VariableOr(v, _) => v.is_empty(),
             Token::Symbol(s) => s.is_empty(),
             Token::PageEnd => false,
             Token::Char(c) => c == &'\0',
             // I think this is the right answer
             Token::SectionStart(_) => false,
+            // An empty comment is still a comment
+            Token::Comment(_) => false,
+            Token::Header(_) => false,
         }
     }
 }
 
 fn parse_symbol(stream: &str) -> Option<(Token, usize)> {
-    let re =
-        Regex::new(r"^\$([^[[:space:]]]+)\$").expect("If this regex is invalid, that is a bug");
+    let re = regex!(
+        r"^\$([^[[:space:]]]+)\$",
+        "If this regex is invalid, that is a bug"
+    );
     re.captures(stream)
         .map(|cap| (Token::Symbol(cap[1].to_string()), cap[0].len()))
 }
@@ -45,32 +113,152 @@ fn parse_symbol(stream: &str) -> Option<(Token, usize)> {
 // A command should take up an entire line
 fn parse_command(stream: &str) -> Option<(Token, usize)> {
     // regex are completely incomprehensible (it doesn't help that I suck at writing them)
-    let re =
-        Regex::new(r"^\{\{[[:space:]]*(\b\w+\b)[[:space:]]*:([^:]*)(: wait_for_kb )?\}\}(\n|$)")
-            .expect("If this is invalid, there is a bug");
-    re.captures(stream).map(|cap| {
-        let name = cap[1].to_string();
-        let arg_list = &cap[2];
-        let arg_list = if arg_list.is_empty() {
-            vec![]
+    let re = regex!(
+        r"^\{\{[[:space:]]*(\b\w+\b)[[:space:]]*:",
+        "If this is invalid, there is a bug"
+    );
+    let cap = re.captures(stream)?;
+    let name = cap[1].to_string();
+    let rest = &stream[cap[0].len()..];
+    let (args, wait, len) = parse_args(rest).or_else(|| parse_block_args(rest))?;
+    let len = cap[0].len() + len;
+    // whatever comes after the command can't be on the same line
+    match stream[len..].chars().next() {
+        None => Some((Token::Command(name, args, wait), len)),
+        Some('\n') => Some((Token::Command(name, args, wait), len + 1)),
+        Some(_) => None,
+    }
+}
+
+// Parses everything in a command after the first ':' up to and including the closing "}}".
+// Returns (args, wait_for_kb?, number of bytes parsed). Arguments are separated by "|,|" and
+// can be put in quotes to use "|,|" or ':' in them (use \" and \\ for quotes and backslashes).
+fn parse_args(stream: &str) -> Option<(Vec<String>, bool, usize)> {
+    const SEP: &str = "|,|";
+    const WAIT: &str = ": wait_for_kb }}";
+
+    // Whether an (unquoted) argument ends at the start of s
+    let ends_arg = |s: &str| {
+        s.starts_with(SEP)
+            || s.starts_with(':')
+            || s.starts_with('\n')
+            || s.starts_with("}}\n")
+            || s == "}}"
+    };
+    // Index of the first thing in stream after idx that isn't a space or tab
+    let skip_space =
+        |idx: usize| stream.len() - stream[idx..].trim_start_matches([' ', '\t']).len();
+
+    let mut args = Vec::new();
+    let mut idx = 0;
+    if stream.starts_with("}}") {
+        return Some((args, false, 2));
+    }
+    loop {
+        let start = skip_space(idx);
+        let rest = &stream[start..];
+        let arg_end = if rest.starts_with('"') {
+            let (arg, len) = parse_quoted(rest)?;
+            args.push(arg);
+            skip_space(start + len)
         } else {
-            arg_list
-                .split("|,|")
-                .map(|s| s.trim().to_string())
-                .collect()
+            let len = rest
+                .char_indices()
+                .map(|(i, _)| i)
+                .find(|&i| ends_arg(&rest[i..]))?;
+            args.push(rest[..len].trim().to_owned());
+            start + len
         };
-        let wait = cap.get(3).map_or("", |mat| mat.as_str());
-        (
-            Token::Command(name, arg_list, !wait.is_empty()),
-            cap[0].len(),
-        )
-    })
+
+        let rest = &stream[arg_end..];
+        if rest.starts_with(SEP) {
+            idx = arg_end + SEP.len();
+        } else if rest.starts_with("}}") {
+            return Some((args, false, arg_end + 2));
+        } else if rest.starts_with(WAIT) {
+            return Some((args, true, arg_end + WAIT.len()));
+        } else {
+            return None;
+        }
+    }
+}
+
+// The block form of a command puts each argument on its own line, e.g.
+// {{ choice_menu :
+//     $ANSWER$
+//     Go back into the cave
+//     Walk away |,| "quickly"
+// }}
+// Separators are just text here, and quotes are only needed for arguments that are exactly "}}"
+// or that have leading/trailing spaces. Blank lines are skipped. Returns the same thing as
+// parse_args.
+fn parse_block_args(stream: &str) -> Option<(Vec<String>, bool, usize)> {
+    const WAIT: &str = ": wait_for_kb }}";
+
+    // Nothing can come after the ':' on the first line
+    let first_line = stream.find('\n')?;
+    if !stream[..first_line].trim().is_empty() {
+        return None;
+    }
+    let mut args = Vec::new();
+    let mut idx = first_line + 1;
+    loop {
+        let end = stream[idx..].find('\n').map_or(stream.len(), |i| idx + i);
+        let line = stream[idx..end].trim();
+        if line == "}}" || line == WAIT {
+            let line_end = stream[..end].trim_end().len();
+            return Some((args, line == WAIT, line_end));
+        } else if end == stream.len() {
+            // never closed
+            return None;
+        } else if line.starts_with('"') {
+            let (arg, len) = parse_quoted(line)?;
+            if len != line.len() {
+                return None;
+            }
+            args.push(arg);
+        } else if !line.is_empty() {
+            args.push(line.to_owned());
+        }
+        idx = end + 1;
+    }
+}
+
+// stream should start with '"'. Returns the unescaped contents and the length including quotes.
+fn parse_quoted(stream: &str) -> Option<(String, usize)> {
+    let mut ret = String::new();
+    let mut chars = stream.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((ret, i + 1)),
+            '\n' => return None,
+            '\\' => match chars.next() {
+                Some((_, e @ '"')) | Some((_, e @ '\\')) => ret.push(e),
+                Some((_, e)) => {
+                    ret.push('\\');
+                    ret.push(e);
+                }
+                None => return None,
+            },
+            c => ret.push(c),
+        }
+    }
+    None
 }
 
 fn parse_variable(stream: &str) -> Option<(Token, usize)> {
-    let re = Regex::new(r"^\$\{\{([^[[:space:]]\{\}]+)\}\}").expect("If bad, then bug");
-    re.captures(stream)
-        .map(|cap| (Token::Variable(cap[1].to_string()), cap[0].len()))
+    let re = regex!(
+        r"^\$\{\{([^[[:space:]]\{\}\|]+)(\|[^\{\}\n]*)?\}\}",
+        "If bad, then bug"
+    );
+    re.captures(stream).map(|cap| {
+        let var = cap[1].to_string();
+        let tkn = match cap.get(2) {
+            Some(def) => Token::VariableOr(var, def.as_str()[1..].to_string()),
+            None => Token::Variable(var),
+        };
+        (tkn, cap[0].len())
+    })
 }
 
 fn parse_pageend(stream: &str) -> Option<(Token, usize)> {
@@ -82,13 +270,13 @@ fn parse_pageend(stream: &str) -> Option<(Token, usize)> {
 }
 
 fn parse_char(stream: &str) -> Option<(Token, usize)> {
-    let re = Regex::new(r"^\{(.)\}").expect("reggie gud");
+    let re = regex!(r"^\{(.)\}", "reggie gud");
     re.captures(stream) // extracting chars from string is my least favorite part of this language
         .map(|cap| (Token::Char(cap[1].chars().next().unwrap()), cap[0].len()))
 }
 
 fn parse_sect_start(stream: &str) -> Option<(Token, usize)> {
-    let re = Regex::new(r"^#=\$ (.*) \$=#(\n|$)").expect("open an issue");
+    let re = regex!(r"^#=\$ (.*) \$=#(\n|$)", "open an issue");
     re.captures(stream).and_then(|cap| {
         let name = cap[1].to_owned();
         name.parse::<usize>()
@@ -97,50 +285,283 @@ fn parse_sect_start(stream: &str) -> Option<(Token, usize)> {
     })
 }
 
+// Everything between {{" and "}} is ordinary text, no matter what it looks like
+pub(crate) fn parse_verbatim(stream: &str) -> Option<(Token, usize)> {
+    let re = regex!(r#"^\{\{"((?s:.)*?)"\}\}"#, "verbatim, this is a bug");
+    re.captures(stream)
+        .map(|cap| (Token::Text(cap[1].to_owned()), cap[0].len()))
+}
+
+// Each line between {{{ and }}} should look like 'key : value'
+fn parse_header(stream: &str) -> Option<(Token, usize)> {
+    let re = regex!(r"^\{\{\{\n((?s:.)*?)\}\}\}(\n|$)", "head(er)ache");
+    let cap = re.captures(stream)?;
+    let fields = cap[1]
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (key, val) = line.split_once(':')?;
+            Some((key.trim().to_owned(), val.trim().to_owned()))
+        })
+        .collect::<Option<_>>()?;
+    Some((Token::Header(fields), cap[0].len()))
+}
+
+// The fields in the header at the start of stream (if there is one)
+pub fn header(stream: &str) -> Option<Vec<(String, String)>> {
+    match parse_header(stream) {
+        Some((Token::Header(fields), _)) => Some(fields),
+        _ => None,
+    }
+}
+
+// Like sections, this should take up an entire line (and the newline goes with it)
+fn parse_line_comment(stream: &str) -> Option<(Token, usize)> {
+    let re = regex!(r"^#~([^\n]*)(\n|$)", "comment on this");
+    re.captures(stream)
+        .map(|cap| (Token::Comment(cap[1].to_owned()), cap[0].len()))
+}
+
+// Can go anywhere and span as many lines as it wants
+fn parse_block_comment(stream: &str) -> Option<(Token, usize)> {
+    let re = regex!(r"^\{\{~((?s:.)*?)~\}\}", "no comment");
+    re.captures(stream)
+        .map(|cap| (Token::Comment(cap[1].to_owned()), cap[0].len()))
+}
+
+type ParseFn = fn(&str) -> Option<(Token, usize)>;
+
+// Tries to parse a special (i.e. not plain text) token at the start of stream. at_start is
+// whether stream is the start of the whole story, and at_line_start whether a line starts there.
+fn parse_special(stream: &str, at_start: bool, at_line_start: bool) -> Option<(Token, usize)> {
+    // (ideally) at most one of these will return Some
+    const PARSE_FUNCS: [ParseFn; 8] = [
+        parse_verbatim,
+        parse_variable,
+        parse_command,
+        parse_symbol,
+        parse_pageend,
+        parse_char,
+        parse_sect_start,
+        parse_block_comment,
+    ];
+
+    let header = at_start.then(|| parse_header(stream)).flatten();
+    let line_comment = at_line_start.then(|| parse_line_comment(stream)).flatten();
+    PARSE_FUNCS
+        .iter()
+        .fold(header.or(line_comment), |acc, f| acc.or_else(|| f(stream)))
+}
+
 pub fn tokenize(stream: &str) -> Vec<Token> {
-    let mut ret = vec![];
+    tokenize_spanned(stream)
+        .into_iter()
+        .map(|(tkn, _)| tkn)
+        .collect()
+}
+
+// Same as tokenize, but also says where in stream each token starts
+pub fn tokenize_spanned(stream: &str) -> Vec<(Token, Pos)> {
+    TokenStream::new(stream.as_bytes())
+        .map(|res| res.expect("a &str is always valid utf-8"))
+        .collect()
+}
+
+// Tokenizes a story as it is read, a line at a time, so the whole thing never has to be in
+// memory at once. Gives the same tokens (and positions) as tokenize_spanned, or an error if
+// reading fails.
+pub struct TokenStream<R> {
+    reader: R,
+    buf: String,                 // read, but not tokenized yet
+    beg: usize,                  // where in buf the text that hasn't been handed out yet starts
+    search_pos: usize,           // where in buf to look for the next special token
+    pos: Pos,                    // always the position of buf[beg..]
+    prev_newline: bool,          // whether the last thing before buf was a newline
+    text: Option<(String, Pos)>, // text that might still merge with more text
+    ready: VecDeque<(Token, Pos)>,
+    eof: bool,
+}
+
+// Whether a token starting at the start of stream could continue onto later lines
+fn spans_lines(stream: &str) -> bool {
+    let re = regex!(
+        r#"^\{\{("|~|\{\n|[[:space:]]*\w+[[:space:]]*:[ \t]*\n)"#,
+        "spans lines, breaks builds"
+    );
+    re.is_match(stream)
+}
 
-    let mut beg = 0;
-    let mut search_pos = beg;
-    while beg < stream.len() {
+// Same as tokenize_spanned, but for a story that hasn't been read yet. Stops early if reading
+// fails, so use TokenStream directly to find out why.
+pub fn tokenize_reader<R: BufRead>(reader: R) -> impl Iterator<Item = (Token, Pos)> {
+    TokenStream::new(reader).map_while(io::Result::ok)
+}
+
+impl<R: BufRead> TokenStream<R> {
+    pub fn new(reader: R) -> Self {
+        TokenStream {
+            reader,
+            buf: String::new(),
+            beg: 0,
+            search_pos: 0,
+            pos: Pos::default(),
+            prev_newline: true,
+            text: None,
+            ready: VecDeque::new(),
+            eof: false,
+        }
+    }
+
+    // Reads one more line into buf. Returns false if there was nothing left to read.
+    fn read_line(&mut self) -> io::Result<bool> {
+        if !self.eof && self.reader.read_line(&mut self.buf)? == 0 {
+            self.eof = true;
+        }
+        Ok(!self.eof)
+    }
+    // Reads until buf has another "}}" after from, since that's how everything that can span
+    // lines ends. Returns false if there was nothing left to read.
+    fn read_past_close(&mut self, from: usize) -> io::Result<bool> {
+        loop {
+            // the "}}" might straddle the old end of buf
+            let checked = self.buf.len().saturating_sub(1).max(from);
+            if !self.read_line()? {
+                return Ok(false);
+            } else if self.buf[checked..].contains("}}") {
+                return Ok(true);
+            }
+        }
+    }
+
+    // Escaped text should blend in with the text around it
+    fn push(&mut self, tkn: Token, pos: Pos) {
+        if tkn.is_empty() {
+            return;
+        }
+        match (&mut self.text, tkn) {
+            (Some((prev, _)), Token::Text(s)) => prev.push_str(&s),
+            (None, Token::Text(s)) => self.text = Some((s, pos)),
+            (_, tkn) => {
+                self.ready
+                    .extend(self.text.take().map(|(s, p)| (Token::Text(s), p)));
+                self.ready.push_back((tkn, pos));
+            }
+        }
+    }
+    // Gets rid of everything in buf that's already been tokenized
+    fn compact(&mut self) {
+        if self.beg > 0 {
+            self.prev_newline = self.buf[..self.beg].ends_with('\n');
+            self.buf.drain(..self.beg);
+            self.search_pos -= self.beg;
+            self.beg = 0;
+        }
+    }
+
+    // Tokenizes (at least) one more line
+    fn step(&mut self) -> io::Result<()> {
+        // Only whole lines get tokenized, since most tokens need to know where lines end
+        if !self.buf[self.search_pos..].contains('\n') {
+            self.compact();
+            self.read_line()?;
+        }
         let special_chars: &[char] = &['{', '$', '/', '#'];
-        if let Some(end) = stream[search_pos..].find(special_chars) {
-            search_pos += end;
-            // (ideally) at most one of these will return Some
-            const PARSE_FUNCS: [fn(&str) -> Option<(Token, usize)>; 6] = [
-                parse_variable,
-                parse_command,
-                parse_symbol,
-                parse_pageend,
-                parse_char,
-                parse_sect_start,
-            ];
-
-            let parsed = PARSE_FUNCS
-                .iter()
-                .fold(None, |acc, f| acc.or_else(|| f(&stream[search_pos..])));
-            if let Some((tkn, len)) = parsed {
-                ret.push(Token::Text(stream[beg..search_pos].to_string()));
-                /*
-                println!(
-                    "parsed token '{:?}' at postion {} after \"{}\"",
-                    tkn,
-                    search_pos,
-                    &stream[beg..search_pos]
-                );
-                */
-                ret.push(tkn);
-                search_pos += len;
-                beg = search_pos;
+        while let Some(end) = self.buf[self.search_pos..].find(special_chars) {
+            self.search_pos += end;
+            let idx = self.search_pos;
+            let at_start = self.pos.offset + idx - self.beg == 0;
+            let at_line_start = if idx == 0 {
+                self.prev_newline
             } else {
-                search_pos += 1;
+                self.buf[..idx].ends_with('\n')
+            };
+            match parse_special(&self.buf[idx..], at_start, at_line_start) {
+                Some((tkn, len)) => {
+                    let text = self.buf[self.beg..idx].to_owned();
+                    self.push(Token::Text(text.clone()), self.pos);
+                    let pos = self.pos.advance(&text);
+                    self.push(tkn, pos);
+                    self.pos = pos.advance(&self.buf[idx..idx + len]);
+                    self.search_pos += len;
+                    self.beg = self.search_pos;
+                }
+                // Whatever starts here might just not have been read all the way yet
+                None if spans_lines(&self.buf[idx..]) && self.read_past_close(idx)? => {}
+                None => self.search_pos += 1,
             }
-        } else {
-            ret.push(Token::Text(stream[beg..].to_owned()));
-            beg = stream.len();
         }
+        // Nothing else special in buf, so the rest is plain text
+        let text = self.buf[self.beg..].to_owned();
+        self.push(Token::Text(text.clone()), self.pos);
+        self.pos = self.pos.advance(&text);
+        self.search_pos = self.buf.len();
+        self.beg = self.search_pos;
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Iterator for TokenStream<R> {
+    type Item = io::Result<(Token, Pos)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.ready.is_empty() {
+            if self.eof && self.search_pos == self.buf.len() {
+                let (text, pos) = self.text.take()?;
+                return Some(Ok((Token::Text(text), pos)));
+            }
+            if let Err(e) = self.step() {
+                // Don't keep trying after the reader fails
+                self.eof = true;
+                self.buf.clear();
+                self.search_pos = 0;
+                self.beg = 0;
+                return Some(Err(e));
+            }
+        }
+        self.ready.pop_front().map(Ok)
+    }
+}
+
+// Turns text into Script that tokenizes back into exactly that text
+pub fn escape_text(text: &str) -> String {
+    let tkns = tokenize(text);
+    if tkns.is_empty() || tkns == [Token::Text(text.to_owned())] {
+        text.to_owned()
+    } else {
+        verbatim(text)
+    }
+}
+
+// Puts text in {{" "}}, whether it needs it or not
+pub(crate) fn verbatim(text: &str) -> String {
+    // Newlines at the end stay outside, so whatever comes next still starts a line
+    let body = text.trim_end_matches('\n');
+    if body.is_empty() {
+        return text.to_owned();
+    }
+    // "}} is the only thing that can't go inside {{" "}}, so split the text there
+    format!(
+        "{{{{\"{}\"}}}}{}",
+        body.replace("\"}}", "\"}}\"{{\"}}"),
+        &text[body.len()..]
+    )
+}
+
+// Turns arg into something that parse_command reads back as arg (written either inline or on a
+// line of its own in the block form). This can't handle newlines.
+pub fn quote_arg(arg: &str) -> String {
+    // "}}" only ends an argument at the end of a line, which an argument never reaches
+    let needs_quotes = arg.is_empty()
+        || arg.trim() != arg
+        || arg.starts_with('"')
+        || arg.contains("|,|")
+        || arg.contains(':')
+        || arg == "}}";
+    if needs_quotes {
+        format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        arg.to_owned()
     }
-    ret.into_iter().filter(|t| !t.is_empty()).collect()
 }
 
 #[cfg(test)]
@@ -308,6 +729,19 @@ mod tests {
             parse_variable("${{_=?!2#232}} huh?"),
             Some((Token::Variable("_=?!2#232".to_string()), 14))
         );
+        assert_eq!(
+            parse_variable("${{NAME|kind stranger}}"),
+            Some((
+                Token::VariableOr("NAME".to_string(), "kind stranger".to_string()),
+                23
+            ))
+        );
+        assert_eq!(
+            parse_variable("${{NAME|}}"),
+            Some((Token::VariableOr("NAME".to_string(), String::new()), 10))
+        );
+        assert_eq!(parse_variable("${{|default}}"), None);
+        assert_eq!(parse_variable("${{NAME|{c}}}"), None);
     }
     #[test]
     fn test_pageend_parsing() {
@@ -356,4 +790,211 @@ mod tests {
         // Section name can't be a usize
         assert_eq!(parse_sect_start("#=$ 42 $=#"), None);
     }
+    #[test]
+    fn test_comment_parsing() {
+        assert_eq!(parse_line_comment("fail"), None);
+        assert_eq!(
+            parse_line_comment("#~ TODO: slow this part down"),
+            Some((Token::Comment(" TODO: slow this part down".to_owned()), 28))
+        );
+        assert_eq!(
+            parse_line_comment("#~ newline goes too\nbut not this"),
+            Some((Token::Comment(" newline goes too".to_owned()), 20))
+        );
+        assert_eq!(
+            parse_line_comment("#~\n"),
+            Some((Token::Comment(String::new()), 3))
+        );
+        assert_eq!(parse_line_comment("# ~ no space allowed"), None);
+
+        assert_eq!(parse_block_comment("fail"), None);
+        assert_eq!(
+            parse_block_comment("{{~ image by me ~}}"),
+            Some((Token::Comment(" image by me ".to_owned()), 19))
+        );
+        assert_eq!(
+            parse_block_comment("{{~ spans\nmultiple\nlines ~}} and then text"),
+            Some((Token::Comment(" spans\nmultiple\nlines ".to_owned()), 28))
+        );
+        assert_eq!(
+            parse_block_comment("{{~ stops ~}} at the {{~ first end ~}}"),
+            Some((Token::Comment(" stops ".to_owned()), 13))
+        );
+        assert_eq!(parse_block_comment("{{~ never ends"), None);
+        assert_eq!(parse_block_comment("{{ ~ not a comment ~ }}"), None);
+    }
+    #[test]
+    fn test_quoted_args() {
+        assert_eq!(
+            parse_command("{{ force_input : \"a |,| b: c\" }}"),
+            Some((
+                Token::Command(
+                    "force_input".to_owned(),
+                    vec!["a |,| b: c".to_owned()],
+                    false
+                ),
+                32
+            ))
+        );
+        assert_eq!(
+            parse_command("{{ cmd : \"  spaces stay  \" |,| plain |,| \"\" : wait_for_kb }}"),
+            Some((
+                Token::Command(
+                    "cmd".to_owned(),
+                    vec![
+                        "  spaces stay  ".to_owned(),
+                        "plain".to_owned(),
+                        String::new()
+                    ],
+                    true
+                ),
+                60
+            ))
+        );
+        assert_eq!(
+            parse_command(r#"{{ say : "she said \"hi\" \\o/" }}"#),
+            Some((
+                Token::Command(
+                    "say".to_owned(),
+                    vec![r#"she said "hi" \o/"#.to_owned()],
+                    false
+                ),
+                34
+            ))
+        );
+        assert_eq!(
+            parse_command("{{ say : \"}} is fine in here\" }}"),
+            Some((
+                Token::Command(
+                    "say".to_owned(),
+                    vec!["}} is fine in here".to_owned()],
+                    false
+                ),
+                32
+            ))
+        );
+        assert_eq!(parse_command("{{ say : \"never closed }}"), None);
+        assert_eq!(parse_command("{{ say : \"quoted\" then junk }}"), None);
+        assert_eq!(parse_command("{{ say : \"no\nnewlines\" }}"), None);
+    }
+    #[test]
+    fn test_commands_stay_on_one_line() {
+        assert_eq!(parse_command("{{ a : b\n}}"), None);
+        assert_eq!(parse_command("{{ a : b }} not\nthe end }}\n"), None);
+        assert_eq!(
+            parse_command("{{ a : ${{X}}}}\nmore"),
+            Some((
+                Token::Command("a".to_owned(), vec!["${{X}}".to_owned()], false),
+                16
+            ))
+        );
+    }
+    #[test]
+    fn test_verbatim_parsing() {
+        assert_eq!(parse_verbatim("fail"), None);
+        assert_eq!(
+            parse_verbatim("{{\"${{NOT_A_VAR}}\"}}"),
+            Some((Token::Text("${{NOT_A_VAR}}".to_owned()), 20))
+        );
+        assert_eq!(
+            parse_verbatim("{{\"/PAGE/ and\n#=$ not a section $=#\"}} after"),
+            Some((
+                Token::Text("/PAGE/ and\n#=$ not a section $=#".to_owned()),
+                38
+            ))
+        );
+        assert_eq!(
+            parse_verbatim("{{\"\"}}"),
+            Some((Token::Text(String::new()), 6))
+        );
+        assert_eq!(parse_verbatim("{{\" never ends"), None);
+    }
+    #[test]
+    fn test_quote_arg_round_trip() {
+        let args = [
+            "plain",
+            "",
+            "  padded ",
+            "a |,| b",
+            "time: 2am",
+            "\"already quoted\"",
+            r"back\slash",
+            "ends in }}",
+            "${{VAR}}",
+        ];
+        let cmd = format!(
+            "{{{{ cmd : {} }}}}",
+            args.iter()
+                .map(|a| quote_arg(a))
+                .collect::<Vec<_>>()
+                .join(" |,| ")
+        );
+        let expected = Token::Command(
+            "cmd".to_owned(),
+            args.iter().map(|&a| a.to_owned()).collect(),
+            false,
+        );
+        assert_eq!(parse_command(&cmd).map(|(tkn, _)| tkn), Some(expected));
+        assert_eq!(quote_arg("plain"), "plain");
+        assert_eq!(quote_arg("${{VAR}}"), "${{VAR}}");
+    }
+    #[test]
+    fn test_header_parsing() {
+        let fields = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|&(k, v)| (k.to_owned(), v.to_owned()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(parse_header("fail"), None);
+        assert_eq!(
+            parse_header("{{{\ntitle : The Cave\nauthor: Me\n\nblurb : Dark: and damp\n}}}\nOnce"),
+            Some((
+                Token::Header(fields(&[
+                    ("title", "The Cave"),
+                    ("author", "Me"),
+                    ("blurb", "Dark: and damp")
+                ])),
+                60
+            ))
+        );
+        assert_eq!(
+            parse_header("{{{\n}}}"),
+            Some((Token::Header(Vec::new()), 7))
+        );
+        assert_eq!(parse_header("{{{\nno colon here\n}}}"), None);
+        assert_eq!(parse_header("{{{ title : same line }}}"), None);
+        assert_eq!(parse_header("{{{\ntitle : never closed\n"), None);
+    }
+    #[test]
+    fn test_block_command_parsing() {
+        let cmd = |name: &str, args: &[&str], wait| {
+            let args = args.iter().map(|&a| a.to_owned()).collect();
+            Token::Command(name.to_owned(), args, wait)
+        };
+        assert_eq!(
+            parse_command("{{ choice_menu :\n  $ANS$\n  Go |,| left\n\n  \"}}\"\n}}\nrest"),
+            Some((
+                cmd("choice_menu", &["$ANS$", "Go |,| left", "}}"], false),
+                50
+            ))
+        );
+        assert_eq!(
+            parse_command("{{ pause :  \n\t1s\n  : wait_for_kb }}"),
+            Some((cmd("pause", &["1s"], true), 35))
+        );
+        assert_eq!(
+            parse_command("{{ wait_kb :\n}}"),
+            Some((cmd("wait_kb", &[], false), 15))
+        );
+        // The block form should give the same token as the inline form
+        assert_eq!(
+            parse_command("{{ backspace :\n 3\n chars\n}}").map(|(t, _)| t),
+            parse_command("{{ backspace : 3 |,| chars }}").map(|(t, _)| t)
+        );
+        assert_eq!(parse_command("{{ pause :\n1s\n"), None);
+        assert_eq!(parse_command("{{ pause :\n1s\n}} more"), None);
+        assert_eq!(parse_command("{{ pause :\n\"1s\" extra\n}}"), None);
+        assert_eq!(parse_command("{{ pause : 1s\n}}"), None);
+    }
 }